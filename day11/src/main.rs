@@ -1,19 +1,10 @@
-use std::sync::mpsc::{Sender, Receiver};
 use std::collections::HashMap;
-use std::thread;
 
 use itertools::{Itertools, MinMaxResult};
 
-mod intcode_pc;
-use intcode_pc::IntcodePC;
+use intcode::{load_prog, IntcodeIo, IntcodePC};
 
-const INPUT: &'static str = include_str!("../input");
-
-fn load_prog() -> Vec<i64> {
-    INPUT.split(",").map(|s| {
-        s.trim().parse::<i64>().unwrap()
-    }).collect()
-}
+const INPUT: &str = include_str!("../input");
 
 enum Dir {
     Up,
@@ -87,7 +78,7 @@ impl Board {
                 };
                 print!("{}", if color == Color::Black { ' ' } else { '#' });
                 if x == max_x {
-                    println!("");
+                    println!();
                 }
             });
     }
@@ -127,17 +118,17 @@ impl Dir {
 struct Picasso {
     dir: Dir,
     pos: Pos,
-    pc_in: Sender<i64>,
-    pc_out: Receiver<i64>,
+    board: Board,
+    painted: bool,
 }
 
 impl Picasso {
-    fn new(pc_in: Sender<i64>, pc_out: Receiver<i64>) -> Self {
+    fn new(board: Board) -> Self {
         Self {
             pos: Pos { x: 0, y: 0 },
             dir: Dir::Up,
-            pc_in,
-            pc_out,
+            board,
+            painted: false,
         }
     }
     
@@ -150,39 +141,34 @@ impl Picasso {
         }
     }
 
-    fn run(&mut self, board: &mut Board) {
-        board.0.insert(self.pos, Color::White); // For part 2
-        loop {
-            let tile_color = if let Some(color) = board.0.get(&self.pos) {
-                *color
-            } else {
-                Color::Black
-            };
-            if let None = self.pc_in.send(tile_color.as_i64()).ok() {
-                break;
-            }
-            if let Some(desired_color) = self.pc_out.recv().ok() {
-                board.0.insert(self.pos, desired_color.into());
-            } else {
-                break;
-            }
-            if let Some(turn) = self.pc_out.recv().ok() {
-                self.dir.turn(turn.into());
-                self.move_forward();
-            } else {
-                break;
-            }
+}
+
+impl IntcodeIo for Picasso {
+    fn input(&mut self) -> Option<i64> {
+        let tile_color = if let Some(color) = self.board.0.get(&self.pos) {
+            *color
+        } else {
+            Color::Black
+        };
+        Some(tile_color.as_i64())
+    }
+
+    fn output(&mut self, value: i64) {
+        if self.painted {
+            self.dir.turn(value.into());
+            self.move_forward();
+        } else {
+            self.board.0.insert(self.pos, value.into());
         }
+        self.painted = !self.painted;
     }
 }
 
 fn main() {
-    let prog = load_prog();
+    let prog = load_prog(INPUT);
     let mut board = Board(HashMap::new());
-    let (pc, input, output) = IntcodePC::new(prog);
-    let mut picasso = Picasso::new(input, output);
-    let pc_handle = thread::spawn(move || pc.run());
-    picasso.run(&mut board);
-    board.print();
-    pc_handle.join().unwrap();
+    board.0.insert(Pos { x: 0, y: 0 }, Color::White); // For part 2
+    let mut picasso = Picasso::new(board);
+    IntcodePC::synchronous(prog).run_io(&mut picasso).unwrap();
+    picasso.board.print();
 }
@@ -0,0 +1,15 @@
+use arcade::{Arcade, FollowBall, INPUT};
+use intcode::{load_prog, IntcodePC};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+
+fn play_benchmark(c: &mut Criterion) {
+    let play = |input: &'static str| {
+        Arcade::free_play(IntcodePC::synchronous(load_prog(input))).unwrap().play(&mut FollowBall).unwrap()
+    };
+
+    c.bench_function("play_benchmark", |b| b.iter(|| play(black_box(INPUT))));
+}
+
+criterion_group!(benches, play_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,394 @@
+use itertools::Itertools;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+
+use intcode::{IntcodeError, IntcodePC, Status};
+use Tile::*;
+
+pub mod recording;
+
+pub const INPUT: &str = include_str!("../input");
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    HorizontalPaddle,
+    Ball,
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match *self {
+                            Empty => ' ',
+                            Wall  => '#',
+                            Block => 'x',
+                            HorizontalPaddle => '-',
+                            Ball  => 'o',
+                        })
+    }
+}
+
+impl Tile {
+    pub fn from_id(id: i64) -> Option<Self> {
+        match id {
+            0 => Some(Empty),
+            1 => Some(Wall),
+            2 => Some(Block),
+            3 => Some(HorizontalPaddle),
+            4 => Some(Ball),
+            _ => None,
+        }
+    }
+}
+
+pub struct Board {
+    width: usize,
+    height: usize,
+    area: HashMap<(usize, usize), Tile>,
+}
+
+impl Board {
+    fn new() -> Self {
+        let width = 1;
+        let height = 1;
+        let area = HashMap::new();
+        Self { width, height, area }
+    }
+
+    fn set_tile(&mut self, x: usize, y: usize, tile: Tile) {
+        if x >= self.width {
+            self.width = x + 1;
+        }
+        if y >= self.height {
+            self.height = y + 1;
+        }
+        self.area.insert((x, y), tile);
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn tile(&self, x: usize, y: usize) -> Tile {
+        self.area.get(&(x, y)).copied().unwrap_or(Empty)
+    }
+
+    /// Position of some `tile`, meant for the ones there's only one of.
+    pub fn find(&self, tile: Tile) -> Option<(usize, usize)> {
+        self.area.iter().find(|(_, &t)| t == tile).map(|(&pos, _)| pos)
+    }
+
+    pub fn count(&self, tile: Tile) -> usize {
+        self.area.values().filter(|&&t| t == tile).count()
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}",
+            (0..self.height).cartesian_product(0..self.width)
+                .map(|(y, x)| format!("{}", self.tile(x, y)))
+                .chunks(self.width).into_iter()
+                .map(|chunk| chunk.collect::<String>())
+                .join("\n"))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Joystick {
+    Left,
+    Neutral,
+    Right,
+}
+
+impl Joystick {
+    pub fn value(self) -> i64 {
+        match self {
+            Joystick::Left => -1,
+            Joystick::Neutral => 0,
+            Joystick::Right => 1,
+        }
+    }
+
+    pub fn from_value(value: i64) -> Option<Self> {
+        match value {
+            -1 => Some(Joystick::Left),
+            0 => Some(Joystick::Neutral),
+            1 => Some(Joystick::Right),
+            _ => None,
+        }
+    }
+}
+
+/// Decides where the joystick goes every time the game asks for it.
+pub trait Controller {
+    fn joystick(&mut self, arcade: &Arcade) -> Joystick;
+
+    /// Stops `Arcade::play` once true, the last move isn't made then.
+    fn quit(&self) -> bool {
+        false
+    }
+}
+
+/// Keeps the paddle under the ball.
+#[derive(Default)]
+pub struct FollowBall;
+
+impl Controller for FollowBall {
+    fn joystick(&mut self, arcade: &Arcade) -> Joystick {
+        use std::cmp::Ordering::*;
+        match (arcade.paddle(), arcade.ball()) {
+            (Some((paddle_x, _)), Some((ball_x, _))) => match paddle_x.cmp(&ball_x) {
+                Less => Joystick::Right,
+                Equal => Joystick::Neutral,
+                Greater => Joystick::Left,
+            },
+            _ => Joystick::Neutral,
+        }
+    }
+}
+
+/// Plays back fixed moves, then leaves the joystick alone.
+pub struct Scripted {
+    moves: VecDeque<Joystick>,
+}
+
+impl Scripted {
+    pub fn new(moves: impl IntoIterator<Item = Joystick>) -> Self {
+        Self { moves: moves.into_iter().collect() }
+    }
+}
+
+impl Controller for Scripted {
+    fn joystick(&mut self, _: &Arcade) -> Joystick {
+        self.moves.pop_front().unwrap_or(Joystick::Neutral)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum State {
+    NeedsInput,
+    GameOver,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ArcadeError {
+    Machine(IntcodeError),
+    UnknownTile { x: i64, y: i64, id: i64 },
+    OffScreen { x: i64, y: i64 },
+    /// There's no cell 0 to insert the quarters into.
+    EmptyProgram,
+}
+
+impl fmt::Display for ArcadeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArcadeError::Machine(e) => write!(f, "{}", e),
+            ArcadeError::UnknownTile { x, y, id } => write!(f, "unknown tile {} at ({}, {})", id, x, y),
+            ArcadeError::OffScreen { x, y } => write!(f, "tile drawn off screen at ({}, {})", x, y),
+            ArcadeError::EmptyProgram => write!(f, "the game program is empty"),
+        }
+    }
+}
+
+impl Error for ArcadeError {}
+
+impl From<IntcodeError> for ArcadeError {
+    fn from(e: IntcodeError) -> Self {
+        ArcadeError::Machine(e)
+    }
+}
+
+/// The cabinet without a screen: runs the game program and keeps track of
+/// the board and the score it draws.
+pub struct Arcade {
+    pc: IntcodePC,
+    board: Board,
+    score: i64,
+    frame: u64,
+    pending: Vec<i64>,
+}
+
+impl Arcade {
+    pub fn new(pc: IntcodePC) -> Self {
+        Self { pc, board: Board::new(), score: 0, frame: 0, pending: vec![] }
+    }
+
+    /// The game loaded in `pc` with quarters inserted.
+    pub fn free_play(mut pc: IntcodePC) -> Result<Self, ArcadeError> {
+        if pc.memory().is_empty() {
+            return Err(ArcadeError::EmptyProgram);
+        }
+        pc.poke(0, 2);
+        Ok(Self::new(pc))
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    /// Joystick moves made so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn ball(&self) -> Option<(usize, usize)> {
+        self.board.find(Ball)
+    }
+
+    pub fn paddle(&self) -> Option<(usize, usize)> {
+        self.board.find(HorizontalPaddle)
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.board.count(Block)
+    }
+
+    fn draw(&mut self, value: i64) -> Result<(), ArcadeError> {
+        self.pending.push(value);
+        if let [x, y, value] = self.pending[..] {
+            self.pending.clear();
+            if (x, y) == (-1, 0) {
+                self.score = value;
+            } else {
+                let tile = Tile::from_id(value).ok_or(ArcadeError::UnknownTile { x, y, id: value })?;
+                match (x.try_into(), y.try_into()) {
+                    (Ok(x), Ok(y)) => self.board.set_tile(x, y, tile),
+                    _ => return Err(ArcadeError::OffScreen { x, y }),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the game until it reads the joystick or ends.
+    pub fn run_frame(&mut self) -> Result<State, ArcadeError> {
+        loop {
+            match self.pc.run_until_io()? {
+                Status::Output(v) => self.draw(v)?,
+                Status::NeedsInput => return Ok(State::NeedsInput),
+                Status::Halted => return Ok(State::GameOver),
+                Status::Running => unreachable!(),
+            }
+        }
+    }
+
+    pub fn push_joystick(&mut self, joystick: Joystick) {
+        self.pc.push_input(joystick.value());
+        self.frame += 1;
+    }
+
+    /// Plays until the game ends or `controller` quits and returns the
+    /// score.
+    pub fn play(&mut self, controller: &mut dyn Controller) -> Result<i64, ArcadeError> {
+        while self.run_frame()? == State::NeedsInput {
+            let joystick = controller.joystick(self);
+            if controller.quit() {
+                break;
+            }
+            self.push_joystick(joystick);
+        }
+        Ok(self.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intcode::load_prog;
+
+    fn game() -> Arcade {
+        Arcade::free_play(IntcodePC::synchronous(load_prog(INPUT))).unwrap()
+    }
+
+    #[test]
+    fn drawing() {
+        // Tiles and a score, the way the game sends them.
+        let prog = vec![104,1,104,2,104,3, 104,-1,104,0,104,42, 104,0,104,0,104,4, 3,100, 99];
+        let mut arcade = Arcade::new(IntcodePC::synchronous(prog));
+        assert_eq!(arcade.run_frame(), Ok(State::NeedsInput));
+        assert_eq!(arcade.score(), 42);
+        assert_eq!(arcade.board().tile(1, 2), HorizontalPaddle);
+        assert_eq!((arcade.ball(), arcade.paddle()), (Some((0, 0)), Some((1, 2))));
+        assert_eq!((arcade.board().width(), arcade.board().height()), (2, 3));
+        assert_eq!(arcade.board().to_string(), "o \n  \n -\n");
+        arcade.push_joystick(Joystick::Left);
+        assert_eq!(arcade.run_frame(), Ok(State::GameOver));
+        assert_eq!(arcade.frame(), 1);
+    }
+
+    #[test]
+    fn bad_drawing() {
+        let mut arcade = Arcade::new(IntcodePC::synchronous(vec![104,1,104,2,104,7, 99]));
+        assert_eq!(arcade.run_frame(), Err(ArcadeError::UnknownTile { x: 1, y: 2, id: 7 }));
+        let mut arcade = Arcade::new(IntcodePC::synchronous(vec![104,-2,104,0,104,1, 99]));
+        assert_eq!(arcade.run_frame(), Err(ArcadeError::OffScreen { x: -2, y: 0 }));
+        let mut arcade = Arcade::new(IntcodePC::synchronous(vec![3,-1]));
+        arcade.push_joystick(Joystick::Neutral);
+        assert!(matches!(arcade.run_frame(), Err(ArcadeError::Machine(IntcodeError::NegativeAddress { .. }))));
+    }
+
+    #[test]
+    fn without_quarters() {
+        let mut arcade = Arcade::new(IntcodePC::synchronous(load_prog(INPUT)));
+        assert_eq!(arcade.run_frame(), Ok(State::GameOver));
+        assert_eq!(arcade.blocks(), 298);
+        assert!(matches!(Arcade::free_play(IntcodePC::synchronous(vec![])), Err(ArcadeError::EmptyProgram)));
+    }
+
+    #[test]
+    fn follow_ball_clears_the_board() {
+        let mut arcade = game();
+        assert_eq!(arcade.play(&mut FollowBall), Ok(13_956));
+        assert_eq!(arcade.blocks(), 0);
+    }
+
+    /// Follows the ball for a few moves, then walks away.
+    struct Impatient {
+        moves: u64,
+        bored: bool,
+    }
+
+    impl Controller for Impatient {
+        fn joystick(&mut self, arcade: &Arcade) -> Joystick {
+            match self.moves {
+                0 => self.bored = true,
+                _ => self.moves -= 1,
+            }
+            FollowBall.joystick(arcade)
+        }
+
+        fn quit(&self) -> bool {
+            self.bored
+        }
+    }
+
+    #[test]
+    fn quitting() {
+        let mut arcade = game();
+        let score = arcade.play(&mut Impatient { moves: 100, bored: false }).unwrap();
+        assert_eq!((arcade.frame(), score), (100, arcade.score()));
+        assert!(arcade.blocks() > 0);
+        assert_eq!(arcade.run_frame(), Ok(State::NeedsInput));
+    }
+
+    #[test]
+    fn idle_joystick_loses() {
+        let mut arcade = game();
+        let score = arcade.play(&mut Scripted::new(vec![Joystick::Right; 3])).unwrap();
+        assert!(arcade.blocks() > 0);
+        assert!(score < 13_956);
+    }
+}
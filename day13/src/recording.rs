@@ -0,0 +1,231 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::{Arcade, ArcadeError, Controller, Joystick, State};
+
+/// Joystick move together with the frame and score it was made at.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Move {
+    pub frame: u64,
+    pub joystick: Joystick,
+    pub score: i64,
+}
+
+/// Every move of a game and the score it ended with. Saved as one
+/// `frame joystick score` line per move and a closing `final score` line.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Recording {
+    pub moves: Vec<Move>,
+    pub final_score: i64,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    /// The recording stops before the final score.
+    Truncated,
+    Machine(ArcadeError),
+    /// The game got somewhere else than it did while recording.
+    Diverged { frame: u64, expected: Option<Move>, score: i64 },
+    FinalScore { expected: i64, actual: i64 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "couldn't read the recording: {}", e),
+            ReplayError::Parse { line, message } =>
+                write!(f, "invalid recording on line {}: {}", line, message),
+            ReplayError::Truncated => write!(f, "the recording is missing the final score"),
+            ReplayError::Machine(e) => write!(f, "{}", e),
+            ReplayError::Diverged { frame, expected, score } =>
+                write!(f, "diverged at frame {} with score {}: expected {:?}", frame, score, expected),
+            ReplayError::FinalScore { expected, actual } =>
+                write!(f, "final score {} instead of {}", actual, expected),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl Recording {
+    pub fn save(&self, mut out: impl Write) -> io::Result<()> {
+        for m in self.moves.iter() {
+            writeln!(out, "{} {} {}", m.frame, m.joystick.value(), m.score)?;
+        }
+        writeln!(out, "final {}", self.final_score)?;
+        out.flush()
+    }
+
+    pub fn load(input: impl BufRead) -> Result<Self, ReplayError> {
+        let mut recording = Recording::default();
+        let mut finished = false;
+        for (n, line) in input.lines().enumerate() {
+            let line = line.map_err(ReplayError::Io)?;
+            let parse_error = |message: &str| ReplayError::Parse { line: n + 1, message: message.to_owned() };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |field: &str| field.parse::<i64>().map_err(|_| parse_error(&format!("{} isn't a number", field)));
+            match fields[..] {
+                [] => continue,
+                _ if finished => return Err(parse_error("moves after the final score")),
+                ["final", score] => {
+                    recording.final_score = number(score)?;
+                    finished = true;
+                },
+                [frame, joystick, score] => {
+                    let joystick = Joystick::from_value(number(joystick)?)
+                        .ok_or_else(|| parse_error("joystick has to be -1, 0 or 1"))?;
+                    let frame = frame.parse::<u64>()
+                        .map_err(|_| parse_error(&format!("{} isn't a frame number", frame)))?;
+                    recording.moves.push(Move { frame, joystick, score: number(score)? });
+                },
+                _ => return Err(parse_error("expected `frame joystick score` or `final score`")),
+            }
+        }
+        if !finished {
+            return Err(ReplayError::Truncated);
+        }
+        Ok(recording)
+    }
+}
+
+/// Passes the moves of `inner` through, writing them down.
+pub struct Recorder<C> {
+    inner: C,
+    moves: Vec<Move>,
+}
+
+impl<C: Controller> Recorder<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner, moves: vec![] }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn finish(self, final_score: i64) -> Recording {
+        Recording { moves: self.moves, final_score }
+    }
+}
+
+impl<C: Controller> Controller for Recorder<C> {
+    fn joystick(&mut self, arcade: &Arcade) -> Joystick {
+        let joystick = self.inner.joystick(arcade);
+        if !self.inner.quit() {
+            self.moves.push(Move { frame: arcade.frame(), joystick, score: arcade.score() });
+        }
+        joystick
+    }
+
+    fn quit(&self) -> bool {
+        self.inner.quit()
+    }
+}
+
+/// Plays `recording` back on `arcade`, checking every frame and the final
+/// score against it. Returns the final score.
+pub fn replay(arcade: &mut Arcade, recording: &Recording) -> Result<i64, ReplayError> {
+    let mut moves = recording.moves.iter();
+    while arcade.run_frame().map_err(ReplayError::Machine)? == State::NeedsInput {
+        let (frame, score) = (arcade.frame(), arcade.score());
+        match moves.next() {
+            Some(m) if (m.frame, m.score) == (frame, score) => arcade.push_joystick(m.joystick),
+            expected => return Err(ReplayError::Diverged { frame, expected: expected.copied(), score }),
+        }
+    }
+    if let Some(&m) = moves.next() {
+        return Err(ReplayError::Diverged { frame: arcade.frame(), expected: Some(m), score: arcade.score() });
+    }
+    match arcade.score() {
+        actual if actual == recording.final_score => Ok(actual),
+        actual => Err(ReplayError::FinalScore { expected: recording.final_score, actual }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FollowBall, Scripted, INPUT};
+    use intcode::{load_prog, IntcodePC};
+
+    fn game() -> Arcade {
+        Arcade::free_play(IntcodePC::synchronous(load_prog(INPUT))).unwrap()
+    }
+
+    fn record(controller: impl Controller) -> Recording {
+        let mut arcade = game();
+        let mut recorder = Recorder::new(controller);
+        let score = arcade.play(&mut recorder).unwrap();
+        recorder.finish(score)
+    }
+
+    #[test]
+    fn round_trip() {
+        let recording = record(FollowBall);
+        assert_eq!(recording.final_score, 13_956);
+        assert_eq!(recording.moves[0], Move { frame: 0, joystick: recording.moves[0].joystick, score: 0 });
+        let mut saved = vec![];
+        recording.save(&mut saved).unwrap();
+        assert!(String::from_utf8_lossy(&saved).ends_with("\nfinal 13956\n"));
+        let loaded = Recording::load(&saved[..]).unwrap();
+        assert_eq!(loaded, recording);
+        assert_eq!(replay(&mut game(), &loaded).unwrap(), 13_956);
+    }
+
+    #[test]
+    fn detects_changes() {
+        let recording = record(FollowBall);
+        let mut wrong_score = recording.clone();
+        wrong_score.final_score += 1;
+        assert!(matches!(replay(&mut game(), &wrong_score),
+            Err(ReplayError::FinalScore { actual: 13_956, .. })));
+
+        let mut short = recording.clone();
+        short.moves.truncate(5);
+        assert!(matches!(replay(&mut game(), &short),
+            Err(ReplayError::Diverged { frame: 5, expected: None, .. })));
+
+        // Letting go of the joystick loses the ball earlier than recorded.
+        let mut idle = recording;
+        idle.moves[100..].iter_mut().for_each(|m| m.joystick = Joystick::Neutral);
+        assert!(matches!(replay(&mut game(), &idle),
+            Err(ReplayError::Diverged { expected: Some(_), .. })));
+
+        let lost = record(Scripted::new(vec![Joystick::Left; 10]));
+        assert_eq!(replay(&mut game(), &lost).unwrap(), lost.final_score);
+    }
+
+    #[test]
+    fn quitting_keeps_the_moves_made() {
+        struct QuitAt(u64, bool);
+        impl Controller for QuitAt {
+            fn joystick(&mut self, arcade: &Arcade) -> Joystick {
+                self.1 = arcade.frame() == self.0;
+                Joystick::Neutral
+            }
+            fn quit(&self) -> bool {
+                self.1
+            }
+        }
+        let recording = record(QuitAt(10, false));
+        assert_eq!(recording.moves.len(), 10);
+        assert_eq!(recording.moves.last().unwrap().frame, 9);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text: &str| match Recording::load(text.as_bytes()) {
+            Err(ReplayError::Parse { line, .. }) => line,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(error("0 0 0\n1 2 0\nfinal 0\n"), 2);
+        assert_eq!(error("0 0 0\nfinal x\n"), 2);
+        assert_eq!(error("final 0\n0 0 0\n"), 2);
+        assert_eq!(error("-1 0 0\nfinal 0\n"), 1);
+        assert!(matches!(Recording::load("0 0 0\n".as_bytes()), Err(ReplayError::Truncated)));
+        assert_eq!(Recording::load("\nfinal 7\n".as_bytes()).unwrap().final_score, 7);
+    }
+}
@@ -0,0 +1,220 @@
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use console::{style, Key, Term};
+
+use arcade::{Arcade, Board, Controller, Joystick, Tile};
+
+/// Frame delays `+` and `-` step through, in milliseconds.
+const SPEEDS: [u64; 9] = [0, 5, 10, 20, 50, 100, 200, 500, 1000];
+
+const HELP: &str = "arrows move, space pauses, n steps, +/- speed, q quits";
+
+/// What the player sees, to redraw only the tiles that changed.
+#[derive(Default)]
+pub struct Shadow {
+    tiles: Vec<Vec<Option<Tile>>>,
+}
+
+impl Shadow {
+    /// Tiles of `board` that differ from the last update.
+    pub fn update(&mut self, board: &Board) -> Vec<(usize, usize, Tile)> {
+        self.tiles.resize(board.height(), vec![]);
+        let mut changes = vec![];
+        for (y, row) in self.tiles.iter_mut().enumerate() {
+            row.resize(board.width(), None);
+            for (x, shown) in row.iter_mut().enumerate() {
+                let tile = board.tile(x, y);
+                if *shown != Some(tile) {
+                    *shown = Some(tile);
+                    changes.push((x, y, tile));
+                }
+            }
+        }
+        changes
+    }
+}
+
+fn styled(tile: Tile) -> String {
+    let text = tile.to_string();
+    match tile {
+        Tile::Empty => text,
+        Tile::Wall => style(text).blue().to_string(),
+        Tile::Block => style(text).yellow().to_string(),
+        Tile::HorizontalPaddle => style(text).green().bold().to_string(),
+        Tile::Ball => style(text).red().bold().to_string(),
+    }
+}
+
+/// Who moves the joystick while the screen is up.
+pub enum Pilot<C> {
+    Keyboard,
+    Auto(C),
+}
+
+/// Interactive screen for the arcade. Keys are read on their own thread so
+/// frames keep coming at the chosen speed whether or not anything is
+/// pressed; with the keyboard piloting the last arrow pressed during a
+/// frame moves the joystick. A failed terminal write quits the game, see
+/// `error`.
+pub struct Tui<C> {
+    term: Term,
+    keys: Receiver<Key>,
+    pilot: Pilot<C>,
+    shadow: Shadow,
+    status: String,
+    speed: usize,
+    paused: bool,
+    quit: bool,
+    error: Option<io::Error>,
+}
+
+impl<C: Controller> Tui<C> {
+    pub fn new(term: Term, pilot: Pilot<C>, delay: u64) -> io::Result<Self> {
+        term.hide_cursor()?;
+        term.clear_screen()?;
+        let (send, keys) = mpsc::channel();
+        let reader = term.clone();
+        // `read_key` can't be interrupted, so the reader is left detached. It
+        // only ever blocks on the terminal and ends at the first key after
+        // the `Tui`, and with it `keys`, is dropped.
+        thread::spawn(move || {
+            while let Ok(key) = reader.read_key() {
+                if send.send(key).is_err() {
+                    break;
+                }
+            }
+        });
+        let speed = SPEEDS.iter().position(|&ms| ms >= delay).unwrap_or(SPEEDS.len() - 1);
+        Ok(Self {
+            term, keys, pilot,
+            shadow: Shadow::default(), status: String::new(),
+            speed, paused: false, quit: false, error: None,
+        })
+    }
+
+    /// The terminal error that ended the game, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Draws the game, or quits it for good when the terminal fails.
+    fn draw(&mut self, arcade: &Arcade) {
+        if let Err(e) = self.try_draw(arcade) {
+            self.error = Some(e);
+            self.quit = true;
+        }
+    }
+
+    fn try_draw(&mut self, arcade: &Arcade) -> io::Result<()> {
+        for (x, y, tile) in self.shadow.update(arcade.board()) {
+            self.term.move_cursor_to(x, y)?;
+            self.term.write_str(&styled(tile))?;
+        }
+        let status = format!("Score: {:<6} Blocks: {:<4} Frame: {:<6} Delay: {}ms{}",
+            arcade.score(), arcade.blocks(), arcade.frame(), SPEEDS[self.speed],
+            if self.paused { "  PAUSED" } else { "" });
+        if status != self.status {
+            self.term.move_cursor_to(0, arcade.board().height())?;
+            self.term.clear_line()?;
+            self.term.write_str(&status)?;
+            self.term.move_cursor_to(0, arcade.board().height() + 1)?;
+            self.term.write_str(HELP)?;
+            self.status = status;
+        }
+        self.term.flush()
+    }
+
+    /// Puts the cursor below the game and back on.
+    pub fn close(&self) -> io::Result<()> {
+        let height = self.shadow.tiles.len();
+        self.term.move_cursor_to(0, height + 2)?;
+        self.term.show_cursor()
+    }
+
+    /// Next key until `deadline`, or until one comes when paused. A closed
+    /// key reader, as when stdin isn't a terminal, never pauses anything.
+    fn next_key(&mut self, deadline: Instant) -> Option<Key> {
+        if self.paused {
+            match self.keys.recv() {
+                Ok(key) => return Some(key),
+                Err(_) => self.paused = false,
+            }
+        }
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.keys.recv_timeout(timeout) {
+            Ok(key) => Some(key),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(timeout);
+                None
+            },
+        }
+    }
+}
+
+impl<C: Controller> Controller for Tui<C> {
+    fn joystick(&mut self, arcade: &Arcade) -> Joystick {
+        self.draw(arcade);
+        if self.quit {
+            return Joystick::Neutral;
+        }
+        let deadline = Instant::now() + Duration::from_millis(SPEEDS[self.speed]);
+        let mut pressed = Joystick::Neutral;
+        while let Some(key) = self.next_key(deadline) {
+            match key {
+                Key::ArrowLeft => pressed = Joystick::Left,
+                Key::ArrowRight => pressed = Joystick::Right,
+                Key::Char(' ') | Key::Char('p') => self.paused = !self.paused,
+                Key::Char('n') if self.paused => break,
+                Key::Char('+') | Key::Char('=') => self.speed = self.speed.saturating_sub(1),
+                Key::Char('-') => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+                Key::Char('q') | Key::Escape => {
+                    self.quit = true;
+                    return Joystick::Neutral;
+                },
+                _ => (),
+            }
+            if self.paused {
+                self.draw(arcade);
+                if self.quit {
+                    return Joystick::Neutral;
+                }
+                // Moving while paused plays a single frame.
+                if pressed != Joystick::Neutral && matches!(self.pilot, Pilot::Keyboard) {
+                    break;
+                }
+            }
+        }
+        match &mut self.pilot {
+            Pilot::Keyboard => pressed,
+            Pilot::Auto(controller) => controller.joystick(arcade),
+        }
+    }
+
+    fn quit(&self) -> bool {
+        self.quit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intcode::IntcodePC;
+
+    #[test]
+    fn redraws_changes_only() {
+        // A ball moving one tile right between two frames.
+        let prog = vec![104,0,104,0,104,1, 104,1,104,0,104,4, 3,100,
+                        104,1,104,0,104,0, 104,2,104,0,104,4, 3,100, 99];
+        let mut arcade = Arcade::new(IntcodePC::synchronous(prog));
+        let mut shadow = Shadow::default();
+        arcade.run_frame().unwrap();
+        assert_eq!(shadow.update(arcade.board()), [(0, 0, Tile::Wall), (1, 0, Tile::Ball)]);
+        assert_eq!(shadow.update(arcade.board()), []);
+        arcade.push_joystick(Joystick::Neutral);
+        arcade.run_frame().unwrap();
+        assert_eq!(shadow.update(arcade.board()), [(1, 0, Tile::Empty), (2, 0, Tile::Ball)]);
+    }
+}
@@ -1,17 +1,14 @@
 use itertools::{Itertools, MinMaxResult};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::thread;
-use std::time::Duration;
 use console::Term;
 use rand::prelude::*;
 use lazy_static::lazy_static;
 
-pub mod intcode_pc;
-use intcode_pc::{load_prog, IntcodePC, Message,};
+use intcode::{load_prog, IntcodeIo, IntcodePC};
 use Tile::*;
 
-const INPUT: &'static str = include_str!("../input");
+const INPUT: &str = include_str!("../input");
 lazy_static! {
     static ref TERM: Term = Term::stdout();
 }
@@ -140,9 +137,9 @@ impl fmt::Display for Board {
         let width = if max.x-min.x == 0 {
             1
         } else {
-            (max.x-min.x).abs() as usize
+            (max.x - min.x).unsigned_abs() as usize
         };
-        write!(f, "{}\n", 
+        writeln!(f, "{}", 
             (min.y..max.y).cartesian_product(min.x..max.x)
                 .map(|(y, x)| {
                     format!("{}", self.get(x, y).unwrap_or(&Tile::Empty))
@@ -195,25 +192,16 @@ impl From<u64> for Move {
     }
 }
 
-struct BoardBuilder<I, O>
-where
-    I: Fn() -> Message,
-    O: Fn(u64) -> Option<()>,
-{
+struct BoardBuilder {
     drone: Vec2,
     board: Board,
     stack: Vec<Move>,
-    input: I,
-    output: O,
+    pending: Option<(Move, bool)>,
     rng: ThreadRng,
 }
 
-impl<I, O> BoardBuilder<I, O>
-where
-    I: Fn() -> Message,
-    O: Fn(u64) -> Option<()>,
-{
-    fn new(input: I, output: O) -> Self {
+impl BoardBuilder {
+    fn new() -> Self {
         let rng = thread_rng();
         let stack: Vec<Move> = vec![];
         let drone = Vec2::zero();
@@ -223,8 +211,7 @@ where
             drone,
             board,
             stack,
-            input,
-            output,
+            pending: None,
             rng,
         }
     }
@@ -232,7 +219,7 @@ where
     fn not_visited(&self) -> HashSet<Move> {
         (1..=4).filter_map(|mov| {
             let mov = mov.into();
-            if let None = self.board.get_pos(self.drone.inspect_move(mov)) {
+            if self.board.get_pos(self.drone.inspect_move(mov)).is_none() {
                 Some(mov)
             } else {
                 None
@@ -255,6 +242,7 @@ where
         }
     }
 
+    #[allow(unused)]
     fn draw(&mut self) {
         let tmp = self.board.get_pos(self.drone).cloned().unwrap();
         self.board.set_tile(self.drone, Tile::Drone);
@@ -263,45 +251,48 @@ where
         self.board.set_tile(self.drone, tmp);
     }
 
-    fn build(mut self) -> Board {
-        while let Message::NeedInput = (self.input)() {
-            let (next_move, stack_popped) = self.next_move();
-            if stack_popped && self.stack.is_empty() {
-                break
-            }
-            (self.output)(next_move.as_u64());
-            match (self.input)() {
-                Message::Value(val) => {
-                    match val {
-                        0 => {
-                            self.board.set_tile(
-                                self.drone.inspect_move(next_move),
-                                Tile::Wall
-                            )
-                        },
-                        tile @ 1..=2 => {
-                            self.drone.apply_move(next_move);
-                            self.board.set_tile(
-                                self.drone,
-                                tile.into()
-                            );
-                            if !stack_popped {
-                                self.stack.push(next_move);
-                            }
-                        },
-                        _ => unreachable!(),
-                    }
-                },
-                Message::Finished => break,
-                Message::NeedInput => unreachable!(),
-            }
-            //thread::sleep(Duration::from_millis(5));
-            //self.draw();
-        }
+    fn build(mut self, mut pc: IntcodePC) -> Board {
+        pc.run_io(&mut self).unwrap();
         self.board
     }
 }
 
+impl IntcodeIo for BoardBuilder {
+    fn input(&mut self) -> Option<i64> {
+        let (next_move, stack_popped) = self.next_move();
+        if stack_popped && self.stack.is_empty() {
+            return None
+        }
+        self.pending = Some((next_move, stack_popped));
+        Some(next_move.as_u64() as i64)
+    }
+
+    fn output(&mut self, val: i64) {
+        let (next_move, stack_popped) = self.pending.take().unwrap();
+        match val {
+            0 => {
+                self.board.set_tile(
+                    self.drone.inspect_move(next_move),
+                    Tile::Wall
+                )
+            },
+            tile @ 1..=2 => {
+                self.drone.apply_move(next_move);
+                self.board.set_tile(
+                    self.drone,
+                    tile.into()
+                );
+                if !stack_popped {
+                    self.stack.push(next_move);
+                }
+            },
+            _ => unreachable!(),
+        }
+        //thread::sleep(Duration::from_millis(5));
+        //self.draw();
+    }
+}
+
 fn bfs_oxygen(board: &Board) -> u32 {
     let mut seen: HashSet<Vec2> = HashSet::new();
     let mut next: HashSet<Vec2> = HashSet::new();
@@ -317,7 +308,7 @@ fn bfs_oxygen(board: &Board) -> u32 {
             .flat_map(|&pos| board.adjanced(pos))
             .filter(|pos| !seen.contains(pos))
             .collect();
-        if let Some(_) = next.iter().find(|&pos| pos == target) {
+        if next.iter().find(|&pos| pos == target).is_some() {
             break
         }
         seen = seen.union(&next).cloned().collect();
@@ -351,15 +342,8 @@ fn bfs_whole_place(board: &Board) -> u32 {
 
 fn main() {
     let prog = load_prog(INPUT);
-    let (pc, pc_in, pc_out) = IntcodePC::new(prog);
-
-    let pc_handle = thread::spawn(move || pc.run());
-
-    let input  = || pc_out.recv().unwrap_or(Message::Finished);
-    let output = |num: u64| pc_in.send(Message::Value(num as i64)).ok();
-    let board = BoardBuilder::new(input, output).build();
+    let board = BoardBuilder::new().build(IntcodePC::synchronous(prog));
     println!("{}", board);
     println!("{}", bfs_oxygen(&board));
     println!("{}", bfs_whole_place(&board));
-    //pc_handle.join().unwrap();
 }
@@ -1,45 +1,25 @@
-const INPUT: &str = include_str!("../input");
-fn load_prog() -> Vec<i32> {
-    INPUT.split(",").map(|s| {
-        s.trim().parse::<i32>().unwrap()
-    }).collect()
-}
+use intcode::load_prog;
+use intcode::solver::{Goal, Search};
 
-fn intcode(mut code: Vec<i32>) -> Vec<i32> {
-    let mut pc = 0;
-    while code[pc] != 99 {
-        let (id1, id2, id3) = (code[pc+1] as usize, code[pc+2] as usize, code[pc+3] as usize);
-        match code[pc] {
-            1 => {
-                code[id3] = code[id1] + code[id2];
-            },
-            2 => {
-                code[id3] = code[id1] * code[id2];
-            },
-            _ => panic!("An error in intcode!"),
-        }
-        pc += 4;
-    }
-    code
-}
+const INPUT: &str = include_str!("../input");
 
 fn main() {
-    for i in 0..=99 {
-        for j in 0..=99 {
-            let mut prog = load_prog();
-            prog[1] = i;
-            prog[2] = j;
-            if intcode(prog)[0] == 19690720 {
-                println!("{}", 100*i+j);
-                break;
-            }
-        }
-    }
+    let search = Search::new(&load_prog(INPUT))
+        .param(1, 0..=99)
+        .param(2, 0..=99)
+        .linear(true);
+    let found = search.solve(Goal::Memory { addr: 0, value: 19690720 }).unwrap();
+    println!("{}", 100*found[0]+found[1]);
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use intcode::IntcodePC;
+
+    fn intcode(code: Vec<i64>) -> Vec<i64> {
+        IntcodePC::new(code).0.run().unwrap().0.to_vec()
+    }
+
     #[test]
     fn first_prog() {
         let prog = vec![1,0,0,0,99];
@@ -1,118 +1,23 @@
-const INPUT: &str = include_str!("../input");
-fn load_prog() -> Vec<i32> {
-    INPUT.split(",").map(|s| {
-        s.trim().parse::<i32>().unwrap()
-    }).collect()
-}
-
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Mode {
-    Immediate,
-    Position,
-}
-
-trait AsMode {
-    fn as_mode(self) -> Mode;
-}
+use intcode::{load_prog, IntcodePC, Message};
 
-impl AsMode for u32 {
-    fn as_mode(self) -> Mode {
-        match self {
-            0 => Mode::Position,
-            1 => Mode::Immediate,
-            _ => panic!("Wrong mode"),
-        }
-    }
-}
-
-#[derive(Debug, PartialEq, Eq)]
-struct Order {
-    ord: i32,
-    params: [Mode; 3],
-}
-
-impl Order {
-    fn new(raw: i32) -> Self {
-        let ord = raw%100;
-        let raw = raw.to_string();
-        let mut params = raw.chars().rev().skip(2).map(|c| c.to_digit(10).unwrap());
-        let params = [params.next().unwrap_or(0).as_mode(),
-                      params.next().unwrap_or(0).as_mode(),
-                      params.next().unwrap_or(0).as_mode()];
-        Self { ord, params }
-    }
-
-    fn execute(self, code: &mut Vec<i32>, pc: &mut usize, input: i32) -> Option<i32> {
-        let args_len = match self.ord {
-            1..=2 => 3,
-            3..=4 => 1,
-            5..=6 => 2,
-            7..=8 => 3,
-            _ => panic!("Wrong order"),
-        };
-        let args: Vec<_> = code[*pc+1..=*pc+args_len]
-            .iter().enumerate()
-            .map(|(n, arg)| {
-                if n == args_len-1 && self.ord != 4 && self.ord != 5 && self.ord != 6 {
-                    *arg
-                } else {
-                    match self.params[n] {
-                        Mode::Position => code[*arg as usize],
-                        Mode::Immediate => *arg,
-                    }
-                }
-            })
-            .collect();
-        match self.ord {
-            1 => { code[args[2] as usize] = args[0] + args[1]; *pc += 4; },
-            2 => { code[args[2] as usize] = args[0] * args[1]; *pc += 4; },
-            3 => { code[args[0] as usize] = input; *pc += 2; },
-            4 => { *pc += 2; return Some(args[0]); },
-            5 => { if args[0] != 0 { *pc = args[1] as usize } else { *pc += 3; } },
-            6 => { if args[0] == 0 { *pc = args[1] as usize } else { *pc += 3; } },
-            7 => { code[args[2] as usize] = if args[0] < args[1] { 1 } else { 0 }; *pc += 4; },
-            8 => { code[args[2] as usize] = if args[0] == args[1] { 1 } else { 0 }; *pc += 4; },
-            _ => (),
-        }
-        None
-    }
-}
+const INPUT: &str = include_str!("../input");
 
-fn intcode(mut code: Vec<i32>, input: i32) -> (Vec<i32>, Vec<i32>) {
-    let mut pc = 0;
-    let mut output = vec![];
-    while code[pc] != 99 {
-        let order = Order::new(code[pc]);
-        if let Some(ret) = order.execute(&mut code, &mut pc, input) {
-            output.push(ret);
-        }
-    }
-    (code, output)
+fn intcode(code: Vec<i64>, input: i64) -> (Vec<i64>, Vec<i64>) {
+    let (pc, pc_in, _pc_out) = IntcodePC::new(code);
+    let _ = pc_in.send(Message::Value(input));
+    let (memory, outputs) = pc.run().unwrap();
+    (memory.to_vec(), outputs)
 }
 
 fn main() {
-    let prog = load_prog();
+    let prog = load_prog(INPUT);
     let (_, output) = intcode(prog, 5);
     println!("{:?}", output);
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{*, Mode::*};
-
-    #[test]
-    fn order_parse() {
-        let code = 11102;
-        assert_eq!(Order::new(code),
-             Order { ord: 2, params: [Immediate, Immediate, Immediate] });
-    }
-
-    #[test]
-    fn order_parse_default_params() {
-        let code = 1001;
-        assert_eq!(Order::new(code),
-             Order { ord: 1, params: [Position, Immediate, Position] });
-    }
+    use super::*;
 
     #[test]
     fn input_output() {
@@ -0,0 +1,117 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use itertools::Itertools;
+
+use intcode::fast::FastPC;
+use intcode::{load_prog, IntcodePC, Status};
+
+trait Engine {
+    fn boot(program: Vec<i64>) -> Self;
+    fn push_input(&mut self, value: i64);
+    fn run_until_io(&mut self) -> Status;
+}
+
+impl Engine for IntcodePC {
+    fn boot(program: Vec<i64>) -> Self {
+        IntcodePC::synchronous(program)
+    }
+
+    fn push_input(&mut self, value: i64) {
+        IntcodePC::push_input(self, value)
+    }
+
+    fn run_until_io(&mut self) -> Status {
+        IntcodePC::run_until_io(self).unwrap()
+    }
+}
+
+impl Engine for FastPC {
+    fn boot(program: Vec<i64>) -> Self {
+        FastPC::new(program)
+    }
+
+    fn push_input(&mut self, value: i64) {
+        FastPC::push_input(self, value)
+    }
+
+    fn run_until_io(&mut self) -> Status {
+        FastPC::run_until_io(self).unwrap()
+    }
+}
+
+/// Day 7 part two: every phase permutation of the feedback loop.
+fn amplifiers<E: Engine>(prog: &[i64]) -> i64 {
+    (5..10).permutations(5)
+        .map(|phases| {
+            let mut amps: Vec<E> = phases.iter()
+                .map(|&phase| {
+                    let mut amp = E::boot(prog.to_vec());
+                    amp.push_input(phase);
+                    amp
+                })
+                .collect();
+            let mut signal = 0;
+            'feedback: loop {
+                for amp in amps.iter_mut() {
+                    amp.push_input(signal);
+                    match amp.run_until_io() {
+                        Status::Output(v) => signal = v,
+                        _ => break 'feedback,
+                    }
+                }
+            }
+            signal
+        })
+        .max()
+        .unwrap()
+}
+
+/// Day 9 part two: the BOOST program in sensor mode.
+fn boost<E: Engine>(prog: &[i64]) -> i64 {
+    let mut pc = E::boot(prog.to_vec());
+    pc.push_input(2);
+    match pc.run_until_io() {
+        Status::Output(v) => v,
+        status => panic!("Unexpected status {:?}", status),
+    }
+}
+
+/// Day 13 part two: a full arcade game with the paddle following the ball.
+fn arcade<E: Engine>(prog: &[i64]) -> i64 {
+    let mut prog = prog.to_vec();
+    prog[0] = 2;
+    let mut pc = E::boot(prog);
+    let (mut ball, mut paddle, mut score) = (0i64, 0, 0);
+    let mut tile = vec![];
+    loop {
+        match pc.run_until_io() {
+            Status::Output(v) => tile.push(v),
+            Status::NeedsInput => pc.push_input((ball - paddle).signum()),
+            _ => break score,
+        }
+        if let [x, y, id] = tile[..] {
+            match (x, y, id) {
+                (-1, 0, _) => score = id,
+                (_, _, 3) => paddle = x,
+                (_, _, 4) => ball = x,
+                _ => (),
+            }
+            tile.clear();
+        }
+    }
+}
+
+fn engines_benchmark(c: &mut Criterion) {
+    let day7 = load_prog(include_str!("../../day7/input"));
+    let day9 = load_prog(include_str!("../../day9/input"));
+    let day13 = load_prog(include_str!("../../day13/input"));
+
+    c.bench_function("day7_interpreter", |b| b.iter(|| amplifiers::<IntcodePC>(black_box(&day7))));
+    c.bench_function("day7_fast", |b| b.iter(|| amplifiers::<FastPC>(black_box(&day7))));
+    c.bench_function("day9_interpreter", |b| b.iter(|| boost::<IntcodePC>(black_box(&day9))));
+    c.bench_function("day9_fast", |b| b.iter(|| boost::<FastPC>(black_box(&day9))));
+    c.bench_function("day13_interpreter", |b| b.iter(|| arcade::<IntcodePC>(black_box(&day13))));
+    c.bench_function("day13_fast", |b| b.iter(|| arcade::<FastPC>(black_box(&day13))));
+}
+
+criterion_group!(benches, engines_benchmark);
+criterion_main!(benches);
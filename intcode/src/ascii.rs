@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+use crate::error::IntcodeError;
+use crate::intcode_pc::{IntcodePC, Status};
+use crate::io::{AsciiIo, IntcodeIo};
+
+/// Everything a program printed until it halted or asked for more input.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AsciiOutput {
+    pub text: String,
+    /// Outputs past 127, usually the puzzle answer.
+    pub values: Vec<i64>,
+    pub status: Status,
+}
+
+/// Text interface to a program speaking ASCII.
+pub struct AsciiPC {
+    pc: IntcodePC,
+}
+
+impl AsciiPC {
+    pub fn new(program: Vec<i64>) -> Self {
+        Self::wrap(IntcodePC::synchronous(program))
+    }
+
+    pub fn wrap(pc: IntcodePC) -> Self {
+        Self { pc }
+    }
+
+    pub fn machine(&mut self) -> &mut IntcodePC {
+        &mut self.pc
+    }
+
+    /// Queues `line` followed by a newline.
+    pub fn send_line(&mut self, line: &str) {
+        line.bytes().chain(Some(b'\n')).for_each(|b| self.pc.push_input(i64::from(b)));
+    }
+
+    pub fn read(&mut self) -> Result<AsciiOutput, IntcodeError> {
+        let mut io = AsciiIo::default();
+        let status = self.pc.run_io(&mut io)?;
+        Ok(AsciiOutput { text: io.text, values: io.values, status })
+    }
+
+    /// Pipes `input` lines to the program and its output to `output` until
+    /// it halts or `input` ends. Non ASCII values are printed on their own
+    /// line.
+    pub fn interact(&mut self, input: impl BufRead, output: impl Write) -> Result<Status, Box<dyn Error>> {
+        let mut terminal = Terminal { input, output, line: Vec::new(), error: None };
+        let status = self.pc.run_io(&mut terminal)?;
+        terminal.output.flush()?;
+        match terminal.error {
+            Some(e) => Err(e.into()),
+            None => Ok(status),
+        }
+    }
+
+    /// `interact` on the process' stdin and stdout.
+    pub fn interactive(&mut self) -> Result<Status, Box<dyn Error>> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.interact(stdin.lock(), stdout.lock())
+    }
+}
+
+struct Terminal<R, W> {
+    input: R,
+    output: W,
+    line: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead, W: Write> Terminal<R, W> {
+    fn fill_line(&mut self) -> io::Result<bool> {
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        self.line = line.into_bytes();
+        self.line.reverse();
+        Ok(true)
+    }
+}
+
+impl<R: BufRead, W: Write> IntcodeIo for Terminal<R, W> {
+    fn input(&mut self) -> Option<i64> {
+        if self.error.is_some() {
+            return None;
+        }
+        if self.line.is_empty() {
+            match self.fill_line() {
+                Ok(true) => (),
+                Ok(false) => return None,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                },
+            }
+        }
+        self.line.pop().map(i64::from)
+    }
+
+    fn output(&mut self, value: i64) {
+        let written = match value {
+            0..=127 => self.output.write_all(&[value as u8]),
+            _ => writeln!(self.output, "{}", value),
+        };
+        if let Err(e) = written {
+            self.error.get_or_insert(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn prompt() -> Vec<i64> {
+        assemble("
+            start:  out  #62
+                    out  #32
+            read:   in   [ch]
+                    eq   [ch], #113, [tmp]
+                    jit  [tmp], #end
+                    out  [ch]
+                    eq   [ch], #10, [tmp]
+                    jif  [tmp], #read
+                    out  #1000
+                    jit  #1, #start
+            end:    hlt
+            ch:     data 0
+            tmp:    data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn lines() {
+        let mut pc = AsciiPC::new(prompt());
+        let out = pc.read().unwrap();
+        assert_eq!((out.text.as_str(), out.status), ("> ", Status::NeedsInput));
+        pc.send_line("hi");
+        assert_eq!(pc.read().unwrap(), AsciiOutput {
+            text: "hi\n> ".to_owned(),
+            values: vec![1000],
+            status: Status::NeedsInput,
+        });
+        pc.send_line("q");
+        assert_eq!(pc.read().unwrap().status, Status::Halted);
+    }
+
+    #[test]
+    fn interactive() {
+        let mut output = vec![];
+        let status = AsciiPC::new(prompt()).interact("ab\ncd".as_bytes(), &mut output).unwrap();
+        assert_eq!(status, Status::NeedsInput);
+        assert_eq!(String::from_utf8(output).unwrap(), "> ab\n1000\n> cd\n1000\n> ");
+
+        let mut output = vec![];
+        let status = AsciiPC::new(prompt()).interact("q\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(status, Status::Halted);
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Mnemonic, opcode, parameter count and index of the written parameter.
+const ORDERS: [(&str, i64, usize, Option<usize>); 10] = [
+    ("add", 1, 3, Some(2)),
+    ("mul", 2, 3, Some(2)),
+    ("in",  3, 1, Some(0)),
+    ("out", 4, 1, None),
+    ("jit", 5, 2, None),
+    ("jif", 6, 2, None),
+    ("lt",  7, 3, Some(2)),
+    ("eq",  8, 3, Some(2)),
+    ("rbo", 9, 1, None),
+    ("hlt", 99, 0, None),
+];
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Label(String, i64),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Position(Expr),
+    Immediate(Expr),
+    Relative(i64),
+}
+
+enum Item {
+    Order { opcode: i64, operands: Vec<Operand> },
+    Data(Vec<Expr>),
+}
+
+struct Line {
+    number: usize,
+    addr: usize,
+    item: Item,
+}
+
+fn err<T>(line: usize, message: impl Into<String>) -> Result<T, AsmError> {
+    Err(AsmError { line, message: message.into() })
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_number(text: &str, line: usize) -> Result<i64, AsmError> {
+    text.trim().replace(' ', "").parse()
+        .or_else(|_| err(line, format!("invalid number '{}'", text.trim())))
+}
+
+fn parse_expr(text: &str, line: usize) -> Result<Expr, AsmError> {
+    let text = text.trim();
+    let split = text.char_indices().skip(1).find(|&(_, c)| c == '+' || c == '-');
+    let (name, offset) = match split {
+        Some((idx, _)) => (text[..idx].trim(), parse_number(&text[idx..], line)?),
+        None => (text, 0),
+    };
+    if is_label(name) {
+        Ok(Expr::Label(name.to_owned(), offset))
+    } else {
+        Ok(Expr::Number(parse_number(text, line)?))
+    }
+}
+
+fn parse_operand(text: &str, line: usize) -> Result<Operand, AsmError> {
+    let text = text.trim();
+    if let Some(imm) = text.strip_prefix('#') {
+        Ok(Operand::Immediate(parse_expr(imm, line)?))
+    } else if text.starts_with('[') && text.ends_with(']') {
+        Ok(Operand::Position(parse_expr(&text[1..text.len()-1], line)?))
+    } else if text == "rb" {
+        Ok(Operand::Relative(0))
+    } else if let Some(offset) = text.strip_prefix("rb") {
+        Ok(Operand::Relative(parse_number(offset, line)?))
+    } else {
+        err(line, format!("invalid operand '{}'", text))
+    }
+}
+
+fn parse(source: &str) -> Result<(Vec<Line>, HashMap<String, usize>), AsmError> {
+    let mut lines = vec![];
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+    for (n, text) in source.lines().enumerate() {
+        let number = n + 1;
+        let mut text = text.split(';').next().unwrap().trim();
+        while let Some(idx) = text.find(':') {
+            let label = text[..idx].trim();
+            if let Ok(expected) = label.parse::<usize>() {
+                if expected != addr {
+                    return err(number, format!("address {} expected, got {}", expected, addr));
+                }
+            } else if is_label(label) {
+                if labels.insert(label.to_owned(), addr).is_some() {
+                    return err(number, format!("label '{}' defined twice", label));
+                }
+            } else {
+                return err(number, format!("invalid label '{}'", label));
+            }
+            text = text[idx+1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (mnemonic, rest) = match text.find(char::is_whitespace) {
+            Some(idx) => (&text[..idx], text[idx..].trim()),
+            None => (text, ""),
+        };
+        let args: Vec<&str> = if rest.is_empty() { vec![] } else { rest.split(',').collect() };
+        let item = if mnemonic == "data" {
+            if args.is_empty() {
+                return err(number, "data needs at least one value");
+            }
+            Item::Data(args.iter().map(|arg| parse_expr(arg, number)).collect::<Result<_, _>>()?)
+        } else {
+            let &(_, opcode, arity, write) = match ORDERS.iter().find(|(name, ..)| *name == mnemonic) {
+                Some(order) => order,
+                None => return err(number, format!("unknown mnemonic '{}'", mnemonic)),
+            };
+            if args.len() != arity {
+                return err(number, format!("{} takes {} operands, got {}", mnemonic, arity, args.len()));
+            }
+            let operands: Vec<Operand> = args.iter()
+                .map(|arg| parse_operand(arg, number))
+                .collect::<Result<_, _>>()?;
+            if let Some(idx) = write {
+                if let Operand::Immediate(_) = operands[idx] {
+                    return err(number, format!("{} cannot write to an immediate", mnemonic));
+                }
+            }
+            Item::Order { opcode, operands }
+        };
+        let size = match &item {
+            Item::Order { operands, .. } => operands.len() + 1,
+            Item::Data(values) => values.len(),
+        };
+        lines.push(Line { number, addr, item });
+        addr += size;
+    }
+    Ok((lines, labels))
+}
+
+fn resolve(expr: &Expr, labels: &HashMap<String, usize>, line: usize) -> Result<i64, AsmError> {
+    match expr {
+        Expr::Number(n) => Ok(*n),
+        Expr::Label(name, offset) => match labels.get(name) {
+            Some(&addr) => Ok(addr as i64 + offset),
+            None => err(line, format!("undefined label '{}'", name)),
+        },
+    }
+}
+
+/// Assembles the textual form into a program as `load_prog` would return it.
+/// Operands are `[addr]` for position, `#value` for immediate and `rb+off`
+/// for relative mode, where `addr` and `value` may be `label` or `label+n`.
+/// `name: data 1, 2` lays out raw cells, `12:` asserts the current address,
+/// which makes the output of `disasm::render` valid input.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let (lines, labels) = parse(source)?;
+    let mut program = vec![];
+    for line in lines {
+        debug_assert_eq!(program.len(), line.addr);
+        match line.item {
+            Item::Data(values) => for value in values {
+                program.push(resolve(&value, &labels, line.number)?);
+            },
+            Item::Order { opcode, operands } => {
+                let mut raw = opcode;
+                let mut args = vec![];
+                for (n, operand) in operands.iter().enumerate() {
+                    let (mode, arg) = match operand {
+                        Operand::Position(expr)  => (0, resolve(expr, &labels, line.number)?),
+                        Operand::Immediate(expr) => (1, resolve(expr, &labels, line.number)?),
+                        Operand::Relative(offset) => (2, *offset),
+                    };
+                    raw += mode * 10i64.pow(n as u32 + 2);
+                    args.push(arg);
+                }
+                program.push(raw);
+                program.append(&mut args);
+            },
+        }
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disasm, load_prog};
+
+    #[test]
+    fn modes() {
+        let prog = assemble("
+            add [4], #3, [5]
+            mul rb-2, #7, rb+1
+            rbo #-3
+            out rb
+            hlt
+        ").unwrap();
+        assert_eq!(prog, [1001,4,3,5, 21202,-2,7,1, 109,-3, 204,0, 99]);
+    }
+
+    #[test]
+    fn labels_and_data() {
+        let prog = assemble("
+            ; compare input with eight
+                    in   [value]
+                    eq   [value], [eight], [value]
+                    out  [value]
+                    hlt
+            value:  data -1
+            eight:  data 8
+        ").unwrap();
+        assert_eq!(prog, [3,9,8,9,10,9,4,9,99,-1,8]);
+    }
+
+    #[test]
+    fn forward_jumps_and_offsets() {
+        let prog = assemble("
+            start: jit #1, #end
+            table: data 10, 20, 30
+            end:   out [table+2]
+                   jif #0, #start
+        ").unwrap();
+        assert_eq!(prog, [1105,1,6, 10,20,30, 4,5, 1106,0,0]);
+    }
+
+    #[test]
+    fn address_assertion() {
+        assert!(assemble("0: out #1\n2: hlt").is_ok());
+        assert_eq!(assemble("0: out #1\n3: hlt"),
+            Err(AsmError { line: 2, message: "address 3 expected, got 2".to_owned() }));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(assemble("nop").unwrap_err().message, "unknown mnemonic 'nop'");
+        assert_eq!(assemble("add #1, #2").unwrap_err().message, "add takes 3 operands, got 2");
+        assert_eq!(assemble("in #5").unwrap_err().message, "in cannot write to an immediate");
+        assert_eq!(assemble("out [nowhere]").unwrap_err().message, "undefined label 'nowhere'");
+        assert_eq!(assemble("a: data 1\na: data 2").unwrap_err(),
+            AsmError { line: 2, message: "label 'a' defined twice".to_owned() });
+        assert_eq!(assemble("out x5").unwrap_err().message, "invalid operand 'x5'");
+    }
+
+    #[test]
+    fn round_trip() {
+        for input in [include_str!("../../day9/input"),
+                      include_str!("../../day13/input"),
+                      include_str!("../../day15/input")].iter() {
+            let prog = load_prog(input);
+            assert_eq!(assemble(&disasm::render(&prog)).unwrap(), prog);
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use futures::channel::mpsc::UnboundedSender;
+use futures::{Stream, StreamExt};
+
+use crate::error::IntcodeError;
+use crate::intcode_pc::{IntcodePC, Status};
+
+/// Instructions executed per poll before the machine gives other tasks a
+/// turn.
+const BUDGET: usize = 4096;
+
+/// Stream of the outputs of an `IntcodePC` reading its input from `input`.
+/// Waiting for input suspends the machine instead of blocking a thread;
+/// the stream ends when the machine halts. If `input` ends while the
+/// machine still wants more, the stream yields `InputClosed` and ends.
+pub struct AsyncPC<I> {
+    pc: IntcodePC,
+    input: I,
+    done: bool,
+}
+
+impl<I: Stream<Item = i64> + Unpin> AsyncPC<I> {
+    pub fn new(pc: IntcodePC, input: I) -> Self {
+        Self { pc, input, done: false }
+    }
+
+    pub fn machine(&self) -> &IntcodePC {
+        &self.pc
+    }
+
+    pub fn into_machine(self) -> IntcodePC {
+        self.pc
+    }
+
+    /// Sends every output to `to` and resolves to the last one once the
+    /// machine halts. `to` is dropped then, closing the channel.
+    pub async fn forward(mut self, to: UnboundedSender<i64>) -> Result<Option<i64>, IntcodeError> {
+        let mut last = None;
+        while let Some(value) = self.next().await {
+            let value = value?;
+            // The receiving machine may have halted already.
+            let _ = to.unbounded_send(value);
+            last = Some(value);
+        }
+        Ok(last)
+    }
+}
+
+impl<I: Stream<Item = i64> + Unpin> Stream for AsyncPC<I> {
+    type Item = Result<i64, IntcodeError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        for _ in 0..BUDGET {
+            let status = match self.pc.step() {
+                Ok(status) => status,
+                Err(e) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                },
+            };
+            match status {
+                Status::Running => (),
+                Status::Output(v) => return Poll::Ready(Some(Ok(v))),
+                Status::Halted => {
+                    self.done = true;
+                    return Poll::Ready(None);
+                },
+                Status::NeedsInput => match Pin::new(&mut self.input).poll_next(cx) {
+                    Poll::Ready(Some(v)) => self.pc.push_input(v),
+                    Poll::Ready(None) => {
+                        self.done = true;
+                        let (pc, raw) = (self.pc.pc(), self.pc.peek(self.pc.pc()));
+                        return Poll::Ready(Some(Err(IntcodeError::InputClosed { pc, raw })));
+                    },
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+/// Single threaded executor, enough to run any number of `AsyncPC`s side
+/// by side without a runtime.
+#[derive(Default)]
+pub struct LocalExecutor {
+    tasks: Vec<Option<Task>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl LocalExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, future: impl Future<Output = ()> + 'static) {
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(future)));
+    }
+
+    /// Polls tasks as they are woken until nothing is left to do. Returns
+    /// how many tasks are still waiting, deadlocked ones included.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let id = match self.ready.lock().unwrap().pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            let waker = Waker::from(Arc::new(TaskWaker { id, ready: self.ready.clone() }));
+            if let Some(task) = self.tasks[id].as_mut() {
+                if task.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                    self.tasks[id] = None;
+                }
+            }
+        }
+        self.tasks.iter().filter(|task| task.is_some()).count()
+    }
+}
+
+/// Runs `future` to completion on a fresh `LocalExecutor`, `None` if it
+/// never completes.
+pub fn block_on<F: Future + 'static>(future: F) -> Option<F::Output> {
+    let result = Rc::new(RefCell::new(None));
+    let slot = result.clone();
+    let mut executor = LocalExecutor::new();
+    executor.spawn(async move {
+        *slot.borrow_mut() = Some(future.await);
+    });
+    executor.run();
+    let output = result.borrow_mut().take();
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc::unbounded;
+    use futures::stream;
+
+    use super::*;
+    use crate::asm::assemble;
+    use crate::intcode_pc::load_prog;
+    use crate::network::Network;
+
+    fn add_one() -> Vec<i64> {
+        assemble("
+                    in   [value]
+                    add  [value], #1, [value]
+                    out  [value]
+                    hlt
+            value:  data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn outputs_stream() {
+        let quine = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        let pc = AsyncPC::new(IntcodePC::synchronous(quine.clone()), stream::empty());
+        let outputs: Vec<_> = block_on(pc.collect::<Vec<_>>()).unwrap();
+        assert_eq!(outputs, quine.into_iter().map(Ok).collect::<Vec<_>>());
+
+        let pc = AsyncPC::new(IntcodePC::synchronous(add_one()), stream::iter(vec![41]));
+        assert_eq!(block_on(pc.collect::<Vec<_>>()), Some(vec![Ok(42)]));
+    }
+
+    #[test]
+    fn input_closed() {
+        let pc = AsyncPC::new(IntcodePC::synchronous(add_one()), stream::empty());
+        assert_eq!(block_on(pc.collect::<Vec<_>>()), Some(vec![Err(IntcodeError::InputClosed { pc: 0, raw: 3 })]));
+    }
+
+    #[test]
+    fn suspends_on_input() {
+        let (send, receive) = unbounded();
+        let mut pc = AsyncPC::new(IntcodePC::synchronous(add_one()), receive);
+        let woken = Arc::new(Mutex::new(VecDeque::new()));
+        let waker = Waker::from(Arc::new(TaskWaker { id: 7, ready: woken.clone() }));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut pc).poll_next(&mut cx), Poll::Pending);
+        assert_eq!(pc.machine().pc(), 0);
+        send.unbounded_send(1).unwrap();
+        assert_eq!(woken.lock().unwrap().pop_front(), Some(7));
+        assert_eq!(Pin::new(&mut pc).poll_next(&mut cx), Poll::Ready(Some(Ok(2))));
+        assert_eq!(Pin::new(&mut pc).poll_next(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn hundreds_of_machines() {
+        let mut executor = LocalExecutor::new();
+        let (first, mut input) = unbounded();
+        for _ in 0..500 {
+            let (send, receive) = unbounded();
+            let pc = AsyncPC::new(IntcodePC::synchronous(add_one()), input);
+            executor.spawn(async move { pc.forward(send).await.unwrap(); });
+            input = receive;
+        }
+        let result = Rc::new(RefCell::new(vec![]));
+        let collected = result.clone();
+        executor.spawn(async move {
+            *collected.borrow_mut() = input.collect::<Vec<_>>().await;
+        });
+        assert_eq!(executor.run(), 501);
+        first.unbounded_send(0).unwrap();
+        drop(first);
+        assert_eq!(executor.run(), 0);
+        assert_eq!(*result.borrow(), [500]);
+    }
+
+    #[test]
+    fn feedback_loop() {
+        let program = load_prog(include_str!("../../day7/input"));
+        let phases = [9, 7, 8, 5, 6];
+        let mut network = Network::ring(&program, 5);
+        phases.iter().enumerate().for_each(|(n, &phase)| network.push_input(n, phase));
+        network.push_input(0, 0);
+        network.run().unwrap();
+        let expected = network.last_output(4);
+
+        let channels: Vec<_> = phases.iter().map(|&phase| {
+            let (send, receive) = unbounded();
+            send.unbounded_send(phase).unwrap();
+            (send, receive)
+        }).collect();
+        channels[0].0.unbounded_send(0).unwrap();
+        let (senders, receivers): (Vec<_>, Vec<_>) = channels.into_iter().unzip();
+        let mut executor = LocalExecutor::new();
+        let last = Rc::new(RefCell::new(None));
+        for (n, receive) in receivers.into_iter().enumerate() {
+            let pc = AsyncPC::new(IntcodePC::synchronous(program.clone()), receive);
+            let (send, last) = (senders[(n + 1) % 5].clone(), last.clone());
+            executor.spawn(async move {
+                let output = pc.forward(send).await.unwrap();
+                if n == 4 {
+                    *last.borrow_mut() = output;
+                }
+            });
+        }
+        drop(senders);
+        assert_eq!(executor.run(), 0);
+        assert_eq!(*last.borrow(), expected);
+    }
+
+    #[test]
+    fn long_computations_yield() {
+        let counter = assemble("
+            loop:   add  [count], #1, [count]
+                    lt   [count], #100000, [tmp]
+                    jit  [tmp], #loop
+                    out  [count]
+                    hlt
+            count:  data 0
+            tmp:    data 0
+        ").unwrap();
+        let order = Rc::new(RefCell::new(vec![]));
+        let mut executor = LocalExecutor::new();
+        let (slow, quick) = (order.clone(), order.clone());
+        let pc = AsyncPC::new(IntcodePC::synchronous(counter), stream::empty());
+        executor.spawn(async move {
+            let outputs: Vec<_> = pc.collect().await;
+            slow.borrow_mut().push(outputs);
+        });
+        let pc = AsyncPC::new(IntcodePC::synchronous(add_one()), stream::iter(vec![1]));
+        executor.spawn(async move {
+            let outputs: Vec<_> = pc.collect().await;
+            quick.borrow_mut().push(outputs);
+        });
+        assert_eq!(executor.run(), 0);
+        assert_eq!(*order.borrow(), [vec![Ok(2)], vec![Ok(100_000)]]);
+    }
+}
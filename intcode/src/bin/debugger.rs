@@ -0,0 +1,39 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use intcode::{debugger::Debugger, read_prog};
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("Usage: debugger <program>");
+        process::exit(2);
+    });
+    let program = read_prog(&path).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}: {}", path, e);
+        process::exit(1);
+    });
+    let mut debugger = Debugger::new(program);
+    println!("{}", debugger.execute("list 0 1"));
+    let stdin = io::stdin();
+    loop {
+        print!("(icdb) ");
+        if io::stdout().flush().is_err() {
+            break;
+        }
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("Couldn't read the command: {}", e);
+                process::exit(1);
+            },
+        }
+        match line.trim() {
+            "" => continue,
+            "q" | "quit" => break,
+            command => println!("{}", debugger.execute(command)),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use std::env;
+use std::process;
+
+use intcode::{disasm, read_prog};
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("Usage: disasm <program>");
+        process::exit(2);
+    });
+    let program = read_prog(&path).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}: {}", path, e);
+        process::exit(1);
+    });
+    println!("{}", disasm::render(&program));
+}
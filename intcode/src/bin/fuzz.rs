@@ -0,0 +1,55 @@
+use std::env;
+use std::process;
+
+use intcode::fuzz::{engines, fuzz, Dialect};
+
+const USAGE: &str = "\
+Usage: fuzz [options]
+
+Runs random programs on every engine and prints the smallest program they
+disagree on.
+
+Options:
+    --seed n        first random seed, default 1
+    --cases n       programs per dialect, default 10000
+    --dialect name  day2, day5 or day9, default all of them
+    --steps n       instructions per run, default 1000";
+
+fn usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| usage(&format!("{} needs a value", name)));
+    value.parse().unwrap_or_else(|_| usage(&format!("{} needs a number, got {}", name, value)))
+}
+
+fn main() {
+    let (mut seed, mut cases, mut steps) = (1, 10_000, 1000);
+    let mut dialects = vec![Dialect::Day2, Dialect::Day5, Dialect::Day9];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = number(&arg, args.next()),
+            "--cases" => cases = number(&arg, args.next()),
+            "--steps" => steps = number(&arg, args.next()),
+            "--dialect" => dialects = vec![match args.next().as_deref() {
+                Some("day2") => Dialect::Day2,
+                Some("day5") => Dialect::Day5,
+                Some("day9") => Dialect::Day9,
+                _ => usage("--dialect needs one of day2, day5 or day9"),
+            }],
+            "-h" | "--help" => usage("Differential fuzzer for the Intcode engines"),
+            _ => usage(&format!("Unexpected argument {}", arg)),
+        }
+    }
+    let engines = engines();
+    for dialect in dialects {
+        if let Some(divergence) = fuzz(seed, cases, dialect, &engines, steps) {
+            println!("{:?} programs diverge (seed {}):\n{}", dialect, seed, divergence);
+            process::exit(1);
+        }
+        println!("{:?}: {} programs, no divergence", dialect, cases);
+    }
+}
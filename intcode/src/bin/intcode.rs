@@ -0,0 +1,168 @@
+use std::env;
+use std::io::{self, Read};
+use std::process;
+use std::time::Duration;
+
+use itertools::Itertools;
+
+use intcode::limits::{Limits, StopReason};
+use intcode::{read_prog, IntcodePC};
+
+const USAGE: &str = "\
+Usage: intcode <program> [options]
+
+Options:
+    -i, --input a,b,c     queue input values, may be repeated
+    -s, --stdin           read input values (or text with --ascii) from stdin
+    -p, --patch addr=val  write a value into memory before running
+    -a, --ascii           read input and print output as ASCII text
+    -m, --memory          print the final memory after the outputs, cells
+                          written past the program as addr=val
+    -c, --counts          report executed instructions on stderr
+    -P, --profile         report hot spots and code coverage on stderr
+    --max-steps n         stop after executing n instructions
+    --max-outputs n       stop after n outputs
+    --max-memory n        fail on writes at or past address n
+    --timeout ms          stop after running for ms milliseconds";
+
+#[derive(Default)]
+struct Options {
+    path: String,
+    inputs: Vec<String>,
+    stdin: bool,
+    patches: Vec<(usize, i64)>,
+    ascii: bool,
+    memory: bool,
+    counts: bool,
+    profile: bool,
+    limits: Limits,
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_patch(patch: &str) -> Option<(usize, i64)> {
+    let (addr, value) = patch.splitn(2, '=').collect_tuple()?;
+    Some((addr.trim().parse().ok()?, value.trim().parse().ok()?))
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: String) -> T {
+    value.parse().unwrap_or_else(|_| usage(&format!("{} needs a number, got {}", name, value)))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().unwrap_or_else(|| usage(&format!("{} needs a value", name)));
+        match arg.as_str() {
+            "-i" | "--input" => options.inputs.push(value(&arg)),
+            "-s" | "--stdin" => options.stdin = true,
+            "-p" | "--patch" => {
+                let patch = value(&arg);
+                let patch = parse_patch(&patch).unwrap_or_else(|| usage(&format!("Invalid patch {}", patch)));
+                options.patches.push(patch);
+            },
+            "-a" | "--ascii" => options.ascii = true,
+            "-m" | "--memory" => options.memory = true,
+            "-c" | "--counts" => options.counts = true,
+            "-P" | "--profile" => options.profile = true,
+            "--max-steps" => options.limits.instructions = Some(number(&arg, value(&arg))),
+            "--max-outputs" => options.limits.outputs = Some(number(&arg, value(&arg))),
+            "--max-memory" => options.limits.memory = Some(number(&arg, value(&arg))),
+            "--timeout" => options.limits.time = Some(Duration::from_millis(number(&arg, value(&arg)))),
+            "-h" | "--help" => usage("Runs an Intcode program"),
+            _ if arg.starts_with('-') => usage(&format!("Unknown option {}", arg)),
+            _ if options.path.is_empty() => options.path = arg,
+            _ => usage(&format!("Unexpected argument {}", arg)),
+        }
+    }
+    if options.path.is_empty() {
+        usage("Missing program path");
+    }
+    options
+}
+
+fn numbers(text: &str) -> Vec<i64> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().unwrap_or_else(|_| usage(&format!("Invalid input value {}", s))))
+        .collect()
+}
+
+fn inputs(options: &Options) -> Vec<i64> {
+    let mut text: Vec<String> = options.inputs.clone();
+    if options.stdin {
+        let mut stdin = String::new();
+        io::stdin().read_to_string(&mut stdin).unwrap();
+        text.push(stdin);
+    }
+    if options.ascii {
+        text.iter().flat_map(|t| t.bytes().map(i64::from).collect::<Vec<_>>()).collect()
+    } else {
+        text.iter().flat_map(|t| numbers(t)).collect()
+    }
+}
+
+fn print_output(value: i64, ascii: bool) {
+    match value {
+        0..=127 if ascii => print!("{}", value as u8 as char),
+        _ => println!("{}", value),
+    }
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1));
+    let program = read_prog(&options.path).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}: {}", options.path, e);
+        process::exit(1);
+    });
+    let mut pc = IntcodePC::synchronous(program);
+    for &(addr, value) in options.patches.iter() {
+        if options.limits.memory.is_some_and(|limit| addr >= limit) {
+            eprintln!("patch at {} is over the memory limit", addr);
+            process::exit(1);
+        }
+        pc.poke(addr, value);
+    }
+    let program = pc.memory().image().program().to_vec();
+    if options.counts || options.profile {
+        pc.enable_profiling();
+        pc.detect_self_modification(false);
+    }
+    inputs(&options).into_iter().for_each(|v| pc.push_input(v));
+
+    let report = pc.run_limited(options.limits);
+    report.outputs.iter().for_each(|&v| print_output(v, options.ascii));
+    if options.memory {
+        let memory = pc.memory().image();
+        println!("{}", memory.program().iter().join(","));
+        memory.written().for_each(|(addr, value)| println!("{}={}", addr, value));
+    }
+    if let Some(profile) = pc.profile() {
+        if options.profile {
+            eprintln!("{}", profile.report(&program));
+        } else {
+            eprintln!("{} instructions executed", profile.instructions());
+            for (op, count) in profile.opcodes().iter().sorted_by_key(|&(_, count)| std::cmp::Reverse(count)) {
+                eprintln!("{:>5} {}", op, count);
+            }
+        }
+    }
+    if let Some(stats) = pc.self_modification_stats() {
+        eprintln!("{} writes to executed code", stats.writes);
+    }
+    let failure = match report.reason {
+        StopReason::Halted => return,
+        StopReason::NeedsInput => format!("no input available at pc {}", pc.pc()),
+        StopReason::InstructionLimit => format!("stopped after {} instructions", report.instructions),
+        StopReason::TimeLimit => format!("stopped after {:?}", report.elapsed),
+        StopReason::OutputLimit => format!("stopped after {} outputs", report.outputs.len()),
+        StopReason::MemoryLimit { addr } => format!("write to {} is over the memory limit", addr),
+        StopReason::Cancelled => "cancelled".to_owned(),
+        StopReason::Error(e) => e.to_string(),
+    };
+    eprintln!("{}", failure);
+    process::exit(1);
+}
@@ -0,0 +1,26 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+use intcode::{read_prog, trace};
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: replay <program> <trace.jsonl>");
+        process::exit(2);
+    }
+    let program = read_prog(&args[1]).unwrap_or_else(|e| fail(format!("Couldn't load {}: {}", args[1], e)));
+    let file = File::open(&args[2]).unwrap_or_else(|e| fail(format!("Couldn't open {}: {}", args[2], e)));
+    let events = trace::read_trace(BufReader::new(file)).unwrap_or_else(|e| fail(e.to_string()));
+    match trace::replay(program, &events) {
+        Ok(outputs) => println!("{} steps replayed, {} outputs match", events.len(), outputs.len()),
+        Err(e) => fail(e.to_string()),
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use crate::disasm;
+use crate::error::IntcodeError;
+use crate::intcode_pc::{IntcodePC, Status};
+
+const HELP: &str = "\
+step [n]          execute n instructions (default 1)
+continue          run until a breakpoint, watchpoint, input request or halt
+break <addr>      stop before executing the instruction at addr
+watch <addr>      stop after the cell at addr changes
+delete <addr>     remove breakpoint and watchpoint at addr
+info              list breakpoints and watchpoints
+x <addr> [n]      show n memory cells starting at addr
+set <addr> <val>  write val to memory
+list [addr] [n]   disassemble n instructions from addr (default pc)
+regs              show pc, relative base and queued input
+input <v>...      queue input values
+help              show this message";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint { addr: usize, old: i64, new: i64 },
+    NeedsInput,
+    Halted,
+    Error(IntcodeError),
+}
+
+pub struct Debugger {
+    pc: IntcodePC,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, i64>,
+    outputs: Vec<i64>,
+}
+
+impl Debugger {
+    pub fn new(program: Vec<i64>) -> Self {
+        Self {
+            pc: IntcodePC::synchronous(program),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            outputs: vec![],
+        }
+    }
+
+    pub fn machine(&self) -> &IntcodePC {
+        &self.pc
+    }
+
+    pub fn outputs(&self) -> &[i64] {
+        &self.outputs
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn add_watchpoint(&mut self, addr: usize) {
+        self.watchpoints.insert(addr, self.pc.peek(addr));
+    }
+
+    fn changed_watchpoint(&mut self) -> Option<Stop> {
+        for (&addr, old) in self.watchpoints.iter_mut() {
+            let new = self.pc.peek(addr);
+            if new != *old {
+                let stop = Stop::Watchpoint { addr, old: *old, new };
+                *old = new;
+                return Some(stop);
+            }
+        }
+        None
+    }
+
+    /// Executes one instruction, outputs are collected on the way.
+    pub fn step(&mut self) -> Stop {
+        match self.pc.step() {
+            Ok(Status::Running) => (),
+            Ok(Status::Output(v)) => self.outputs.push(v),
+            Ok(Status::NeedsInput) => return Stop::NeedsInput,
+            Ok(Status::Halted) => return Stop::Halted,
+            Err(e) => return Stop::Error(e),
+        }
+        self.changed_watchpoint().unwrap_or(Stop::Stepped)
+    }
+
+    /// Runs until something interesting happens. A breakpoint at the current
+    /// pc doesn't stop the first instruction, so `continue` can leave it.
+    pub fn resume(&mut self) -> Stop {
+        loop {
+            match self.step() {
+                Stop::Stepped => (),
+                stop => break stop,
+            }
+            if self.breakpoints.contains(&self.pc.pc()) {
+                break Stop::Breakpoint(self.pc.pc());
+            }
+        }
+    }
+
+    fn location(&self) -> String {
+        format!("{}", disasm::line_in(self.pc.memory(), self.pc.pc()))
+    }
+
+    fn report(&mut self, stop: Stop, printed_outputs: usize) -> String {
+        let mut ret = String::new();
+        if self.outputs.len() > printed_outputs {
+            writeln!(ret, "output: {}", self.outputs[printed_outputs..].iter().join(", ")).unwrap();
+        }
+        match stop {
+            Stop::Stepped => (),
+            Stop::Breakpoint(addr) => writeln!(ret, "breakpoint at {}", addr).unwrap(),
+            Stop::Watchpoint { addr, old, new } =>
+                writeln!(ret, "watchpoint [{}]: {} -> {}", addr, old, new).unwrap(),
+            Stop::NeedsInput => writeln!(ret, "waiting for input").unwrap(),
+            Stop::Halted => writeln!(ret, "halted").unwrap(),
+            Stop::Error(e) => writeln!(ret, "error: {}", e).unwrap(),
+        }
+        ret + &self.location()
+    }
+
+    /// Runs a single REPL command and returns what should be printed.
+    pub fn execute(&mut self, command: &str) -> String {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Result<Vec<i64>, _> = words.map(|w| w.parse::<i64>()).collect();
+        let args = match args {
+            Ok(args) => args,
+            Err(_) => return "arguments have to be numbers".to_owned(),
+        };
+        let addr = |n: usize| args.get(n).filter(|&&a| a >= 0).map(|&a| a as usize);
+        let printed = self.outputs.len();
+        match (name, args.len()) {
+            ("s", _) | ("step", _) => {
+                let mut stop = Stop::Stepped;
+                for _ in 0..args.first().cloned().unwrap_or(1).max(1) {
+                    stop = self.step();
+                    if stop != Stop::Stepped {
+                        break;
+                    }
+                }
+                self.report(stop, printed)
+            },
+            ("c", 0) | ("continue", 0) => {
+                let stop = self.resume();
+                self.report(stop, printed)
+            },
+            ("b", 1) | ("break", 1) if addr(0).is_some() => {
+                self.add_breakpoint(addr(0).unwrap());
+                format!("breakpoint at {}", args[0])
+            },
+            ("w", 1) | ("watch", 1) if addr(0).is_some() => {
+                self.add_watchpoint(addr(0).unwrap());
+                format!("watching [{}] = {}", args[0], self.pc.peek(args[0] as usize))
+            },
+            ("d", 1) | ("delete", 1) if addr(0).is_some() => {
+                let removed = self.breakpoints.remove(&addr(0).unwrap()) as u8
+                    + self.watchpoints.remove(&addr(0).unwrap()).is_some() as u8;
+                format!("removed {} stop point(s) at {}", removed, args[0])
+            },
+            ("info", 0) => format!("breakpoints: {}\nwatchpoints: {}",
+                self.breakpoints.iter().join(", "),
+                self.watchpoints.keys().join(", ")),
+            ("x", 1) | ("x", 2) if addr(0).is_some() => {
+                let start = addr(0).unwrap();
+                (start..start + args.get(1).cloned().unwrap_or(1).max(1) as usize)
+                    .map(|a| format!("[{}] = {}", a, self.pc.peek(a)))
+                    .join("\n")
+            },
+            ("set", 2) if addr(0).is_some() => {
+                self.pc.poke(addr(0).unwrap(), args[1]);
+                if let Some(old) = self.watchpoints.get_mut(&addr(0).unwrap()) {
+                    *old = args[1];
+                }
+                format!("[{}] = {}", args[0], args[1])
+            },
+            ("l", n) | ("list", n) if n <= 2 => {
+                let mut at = addr(0).unwrap_or_else(|| self.pc.pc());
+                let memory = self.pc.memory();
+                let mut lines = vec![];
+                for _ in 0..args.get(1).cloned().unwrap_or(10) {
+                    let line = disasm::line_in(memory, at);
+                    at += line.size();
+                    lines.push(format!("{}", line));
+                    if at >= memory.len() {
+                        break;
+                    }
+                }
+                lines.join("\n")
+            },
+            ("regs", 0) => format!("pc: {}, rel_base: {}, input: [{}]",
+                self.pc.pc(), self.pc.rel_base(), self.pc.pending_input().iter().join(", ")),
+            ("i", _) | ("input", _) if !args.is_empty() => {
+                args.iter().for_each(|&v| self.pc.push_input(v));
+                format!("queued {} value(s)", args.len())
+            },
+            ("help", _) => HELP.to_owned(),
+            _ => format!("unknown command '{}', try 'help'", command.trim()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn compare_prog() -> Vec<i64> {
+        assemble("
+                    in   [value]
+                    eq   [value], #8, [value]
+                    out  [value]
+                    hlt
+            value:  data -1
+        ").unwrap()
+    }
+
+    #[test]
+    fn step_and_regs() {
+        let mut debugger = Debugger::new(compare_prog());
+        assert!(debugger.execute("step").starts_with("waiting for input"));
+        debugger.execute("input 8");
+        assert_eq!(debugger.execute("regs"), "pc: 0, rel_base: 0, input: [8]");
+        debugger.execute("s 2");
+        assert_eq!(debugger.machine().pc(), 6);
+        assert_eq!(debugger.execute("x 9"), "[9] = 1");
+        assert!(debugger.execute("step").starts_with("output: 1\n"));
+        assert!(debugger.execute("c").starts_with("halted"));
+    }
+
+    #[test]
+    fn breakpoint() {
+        let mut debugger = Debugger::new(compare_prog());
+        debugger.execute("input 3");
+        debugger.execute("break 6");
+        assert!(debugger.execute("continue").starts_with("breakpoint at 6\n    6: out"));
+        assert!(debugger.outputs().is_empty());
+        assert!(debugger.execute("continue").starts_with("output: 0\nhalted"));
+    }
+
+    #[test]
+    fn watchpoint_and_set() {
+        let mut debugger = Debugger::new(compare_prog());
+        debugger.execute("input 5");
+        debugger.execute("watch 9");
+        assert!(debugger.execute("c").starts_with("watchpoint [9]: -1 -> 5"));
+        assert!(debugger.execute("c").starts_with("watchpoint [9]: 5 -> 0"));
+        debugger.execute("set 9 42");
+        assert!(debugger.execute("c").starts_with("output: 42\nhalted"));
+        debugger.execute("delete 9");
+        assert_eq!(debugger.execute("info"), "breakpoints: \nwatchpoints: ");
+    }
+
+    #[test]
+    fn far_code() {
+        // Runs code written at 10^9 without copying memory on every stop.
+        let mut debugger = Debugger::new(vec![1101,99,0,1_000_000_000, 1105,1,1_000_000_000]);
+        let hlt = "1000000000: hlt                              ; 99";
+        assert_eq!(debugger.execute("s 2"), hlt);
+        assert_eq!(debugger.execute("l 1000000000 5"), hlt);
+        assert!(debugger.execute("c").starts_with("halted"));
+    }
+
+    #[test]
+    fn errors_stop_the_machine() {
+        let mut debugger = Debugger::new(vec![42]);
+        assert!(debugger.execute("c").starts_with("error: unknown opcode in 42 at pc 0"));
+        assert!(debugger.execute("x a").starts_with("arguments have to be numbers"));
+        assert!(debugger.execute("jump").starts_with("unknown command 'jump'"));
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::intcode_pc::{Mode, Order};
+use crate::memory::Memory;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Line {
+    Code { addr: usize, words: Vec<i64> },
+    Data { addr: usize, value: i64 },
+}
+
+impl Line {
+    pub fn addr(&self) -> usize {
+        match *self {
+            Line::Code { addr, .. } | Line::Data { addr, .. } => addr,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Line::Code { words, .. } => words.len(),
+            Line::Data { .. } => 1,
+        }
+    }
+}
+
+pub(crate) fn mnemonic(order: &Order) -> &'static str {
+    use Order::*;
+    match order {
+        Add {..} => "add",
+        Mul {..} => "mul",
+        In  {..} => "in",
+        Out {..} => "out",
+        Jit {..} => "jit",
+        Jif {..} => "jif",
+        Lt  {..} => "lt",
+        Eq  {..} => "eq",
+        Rbo {..} => "rbo",
+    }
+}
+
+fn operand(mode: Mode, arg: i64) -> String {
+    match mode {
+        Mode::Position | Mode::ReturnAddr => format!("[{}]", arg),
+        Mode::Immediate => format!("#{}", arg),
+        Mode::Relative | Mode::ReturnAddrRelative => format!("rb{:+}", arg),
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (addr, text, words) = match self {
+            Line::Code { addr, words } if words == &[99] => (addr, "hlt".to_owned(), words.clone()),
+            Line::Code { addr, words } => {
+                let order = Order::new(words[0], *addr).unwrap();
+                let operands = order.modes().into_iter()
+                    .zip(&words[1..])
+                    .map(|(mode, &arg)| operand(mode, arg))
+                    .join(", ");
+                (addr, format!("{:<4} {}", mnemonic(&order), operands), words.clone())
+            },
+            Line::Data { addr, value } => (addr, format!("data {}", value), vec![*value]),
+        };
+        write!(f, "{:>5}: {:<32} ; {}", addr, text, words.iter().join(","))
+    }
+}
+
+/// Decodes the instruction at `addr`, `None` when it's not a valid
+/// instruction, has mode digits for parameters it doesn't take or its
+/// parameters run past the end of the program.
+fn decode(program: &[i64], addr: usize) -> Option<(Option<Order>, usize)> {
+    let raw = *program.get(addr)?;
+    if raw == 99 {
+        return Some((None, 1));
+    }
+    let order = Order::new(raw, addr).ok()?;
+    let len = order.modes().len() + 1;
+    if raw >= 10i64.pow(len as u32 + 1) || addr + len > program.len() {
+        return None;
+    }
+    Some((Some(order), len))
+}
+
+/// Follows control flow from address 0 and from every immediate jump target.
+/// Constants computed from two immediates and stored relative to the base
+/// (`add #ret, #0, rb+0`, `mul #1, #ret, rb+0`) are treated as pushed return
+/// addresses, so code after a call is still reached. Everything not reached
+/// is marked as data.
+fn code_starts(program: &[i64]) -> BTreeSet<usize> {
+    use Order::*;
+    let mut covered = vec![false; program.len()];
+    let mut starts = BTreeSet::new();
+    let mut pending = vec![0];
+    while let Some(mut addr) = pending.pop() {
+        while let Some((order, len)) = decode(program, addr) {
+            if covered[addr..addr+len].iter().any(|&c| c) {
+                break;
+            }
+            covered[addr..addr+len].iter_mut().for_each(|c| *c = true);
+            starts.insert(addr);
+            let args = &program[addr+1..addr+len];
+            let fallthrough = match order {
+                None => false,
+                Some(Jit { test, addr: target }) | Some(Jif { test, addr: target }) => {
+                    if target == Mode::Immediate && args[1] >= 0 {
+                        pending.push(args[1] as usize);
+                    }
+                    let nonzero_test = matches!(order, Some(Jit {..}));
+                    !(test == Mode::Immediate && (args[0] != 0) == nonzero_test)
+                },
+                Some(Add { a: Mode::Immediate, b: Mode::Immediate, res_addr: Mode::ReturnAddrRelative })
+                | Some(Mul { a: Mode::Immediate, b: Mode::Immediate, res_addr: Mode::ReturnAddrRelative }) => {
+                    let target = if let Some(Add {..}) = order {
+                        args[0].checked_add(args[1])
+                    } else {
+                        args[0].checked_mul(args[1])
+                    };
+                    if let Some(target) = target.filter(|&t| t >= 0 && (t as usize) < program.len()) {
+                        pending.push(target as usize);
+                    }
+                    true
+                },
+                Some(_) => true,
+            };
+            if !fallthrough {
+                break;
+            }
+            addr += len;
+        }
+    }
+    starts
+}
+
+/// Decodes a single cell as an instruction when possible, regardless of
+/// whether it's reachable.
+pub fn line_at(program: &[i64], addr: usize) -> Line {
+    match decode(program, addr) {
+        Some((_, len)) => Line::Code { addr, words: program[addr..addr+len].to_vec() },
+        None => Line::Data { addr, value: program.get(addr).cloned().unwrap_or(0) },
+    }
+}
+
+/// `line_at` reading only the few cells an instruction at `addr` can span,
+/// for memory too large to copy.
+pub fn line_in(memory: &dyn Memory, addr: usize) -> Line {
+    let words: Vec<i64> = (addr..(addr + 4).min(memory.len())).map(|a| memory.get(a)).collect();
+    match line_at(&words, 0) {
+        Line::Code { words, .. } => Line::Code { addr, words },
+        Line::Data { value, .. } => Line::Data { addr, value },
+    }
+}
+
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let starts = code_starts(program);
+    let mut lines = vec![];
+    let mut addr = 0;
+    while addr < program.len() {
+        if starts.contains(&addr) {
+            let (_, len) = decode(program, addr).unwrap();
+            lines.push(Line::Code { addr, words: program[addr..addr+len].to_vec() });
+            addr += len;
+        } else {
+            lines.push(Line::Data { addr, value: program[addr] });
+            addr += 1;
+        }
+    }
+    lines
+}
+
+pub fn render(program: &[i64]) -> String {
+    disassemble(program).iter().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operands() {
+        let lines = disassemble(&[1001,4,3,5, 21202,-2,7,1, 99]);
+        assert_eq!(format!("{}", lines[0]).split(';').next().unwrap().trim_end(),
+            "    0: add  [4], #3, [5]");
+        assert_eq!(format!("{}", lines[1]).split(';').next().unwrap().trim_end(),
+            "    4: mul  rb-2, #7, rb+1");
+        assert_eq!(format!("{}", lines[2]), format!("{:>5}: {:<32} ; 99", 8, "hlt"));
+    }
+
+    #[test]
+    fn data_after_halt() {
+        let lines = disassemble(&[3,9,8,9,10,9,4,9,99,-1,8]);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[3], Line::Code { addr: 8, words: vec![99] });
+        assert_eq!(lines[4], Line::Data { addr: 9, value: -1 });
+        assert_eq!(lines[5], Line::Data { addr: 10, value: 8 });
+    }
+
+    #[test]
+    fn extra_mode_digits_are_data() {
+        let lines = disassemble(&[10104,1,99]);
+        assert_eq!(lines[0], Line::Data { addr: 0, value: 10104 });
+    }
+
+    #[test]
+    fn follows_jumps() {
+        // jump over a data cell which happens to look like an instruction
+        let lines = disassemble(&[1105,1,4,1,104,7,99]);
+        assert_eq!(lines, vec![
+            Line::Code { addr: 0, words: vec![1105,1,4] },
+            Line::Data { addr: 3, value: 1 },
+            Line::Code { addr: 4, words: vec![104,7] },
+            Line::Code { addr: 6, words: vec![99] },
+        ]);
+    }
+
+    #[test]
+    fn pushed_return_address() {
+        // call: push #9 as return address, jump to 10; function returns via rb+0
+        let prog = vec![109,20, 21102,1,9,0, 1105,1,10, 99, 104,1, 2106,0,0];
+        let lines = disassemble(&prog);
+        assert!(lines.contains(&Line::Code { addr: 9, words: vec![99] }));
+        assert!(lines.contains(&Line::Code { addr: 12, words: vec![2106,0,0] }));
+    }
+
+    #[test]
+    fn covers_whole_program() {
+        let prog = crate::load_prog(include_str!("../../day9/input"));
+        let lines = disassemble(&prog);
+        assert_eq!(lines.iter().map(Line::size).sum::<usize>(), prog.len());
+    }
+}
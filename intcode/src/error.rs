@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IntcodeError {
+    UnknownOpcode { pc: usize, raw: i64 },
+    InvalidMode { pc: usize, raw: i64, mode: u32 },
+    ImmediateWrite { pc: usize, raw: i64 },
+    NegativeAddress { pc: usize, raw: i64, addr: i64 },
+    UnexpectedMessage { pc: usize, raw: i64 },
+    InputClosed { pc: usize, raw: i64 },
+    MemoryLimit { pc: usize, raw: i64, addr: usize, limit: usize },
+    /// An `add`, `mul` or relative base adjustment left the `i64` range.
+    /// Both `IntcodePC` and `FastPC` stop here rather than wrap, there is no
+    /// wider word mode.
+    Overflow { pc: usize, raw: i64 },
+    SelfModifying { pc: usize, raw: i64, addr: usize },
+}
+
+impl IntcodeError {
+    pub fn pc(&self) -> usize {
+        use IntcodeError::*;
+        match *self {
+            UnknownOpcode { pc, .. }
+            | InvalidMode { pc, .. }
+            | ImmediateWrite { pc, .. }
+            | NegativeAddress { pc, .. }
+            | UnexpectedMessage { pc, .. }
+            | InputClosed { pc, .. }
+            | MemoryLimit { pc, .. }
+            | Overflow { pc, .. }
+            | SelfModifying { pc, .. } => pc,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use IntcodeError::*;
+        match *self {
+            UnknownOpcode { pc, raw } =>
+                write!(f, "unknown opcode in {} at pc {}", raw, pc),
+            InvalidMode { pc, raw, mode } =>
+                write!(f, "invalid parameter mode {} in {} at pc {}", mode, raw, pc),
+            ImmediateWrite { pc, raw } =>
+                write!(f, "write to an immediate parameter in {} at pc {}", raw, pc),
+            NegativeAddress { pc, raw, addr } =>
+                write!(f, "negative address {} used by {} at pc {}", addr, raw, pc),
+            UnexpectedMessage { pc, raw } =>
+                write!(f, "received a non value message for {} at pc {}", raw, pc),
+            InputClosed { pc, raw } =>
+                write!(f, "no input available for {} at pc {}", raw, pc),
+            MemoryLimit { pc, raw, addr, limit } =>
+                write!(f, "address {} is over the {} cell memory limit in {} at pc {}", addr, limit, raw, pc),
+            Overflow { pc, raw } =>
+                write!(f, "arithmetic overflow in {} at pc {}", raw, pc),
+            SelfModifying { pc, raw, addr } =>
+                write!(f, "write to executed code at {} by {} at pc {}", addr, raw, pc),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,360 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::error::IntcodeError;
+use crate::intcode_pc::Status;
+use crate::memory::Image;
+
+const POSITION: u8 = 0;
+const IMMEDIATE: u8 = 1;
+const RELATIVE: u8 = 2;
+
+/// Memory grows as a vector up to this many cells, writes further out go
+/// to a map.
+const DENSE_CELLS: usize = 1 << 20;
+
+/// Opcode and parameter modes of a single instruction, decoded once per
+/// address and reused until something writes over the opcode.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Decoded {
+    op: u8,
+    modes: [u8; 3],
+}
+
+impl Decoded {
+    fn new(raw: i64, pc: usize) -> Result<Self, IntcodeError> {
+        if raw == 99 {
+            return Ok(Self { op: 99, modes: [0; 3] });
+        }
+        if !(0..100_000).contains(&raw) {
+            return Err(IntcodeError::UnknownOpcode { pc, raw });
+        }
+        let mut modes = [0; 3];
+        let mut rest = raw / 100;
+        for mode in modes.iter_mut() {
+            *mode = (rest % 10) as u8;
+            if *mode > RELATIVE {
+                return Err(IntcodeError::InvalidMode { pc, raw, mode: *mode as u32 });
+            }
+            rest /= 10;
+        }
+        let op = (raw % 100) as u8;
+        let write = match op {
+            1 | 2 | 7 | 8 => Some(2),
+            3 => Some(0),
+            4 | 5 | 6 | 9 => None,
+            _ => return Err(IntcodeError::UnknownOpcode { pc, raw }),
+        };
+        if write.is_some_and(|n| modes[n] == IMMEDIATE) {
+            return Err(IntcodeError::ImmediateWrite { pc, raw });
+        }
+        Ok(Self { op, modes })
+    }
+}
+
+/// Synchronous machine with the same semantics as `IntcodePC`, tuned for
+/// speed: opcodes are decoded arithmetically and cached, and a step
+/// doesn't allocate unless memory has to grow.
+pub struct FastPC {
+    memory: Vec<i64>,
+    far: HashMap<usize, i64>,
+    decoded: Vec<Option<Decoded>>,
+    pc: usize,
+    rel_base: i64,
+    inputs: VecDeque<i64>,
+    limit: usize,
+}
+
+impl FastPC {
+    pub fn new(program: Vec<i64>) -> Self {
+        Self {
+            decoded: vec![None; program.len()],
+            memory: program,
+            far: HashMap::new(),
+            pc: 0,
+            rel_base: 0,
+            inputs: VecDeque::new(),
+            limit: usize::MAX,
+        }
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.inputs.push_back(value);
+    }
+
+    /// Writes at or past `cells` fail with `IntcodeError::MemoryLimit`.
+    pub fn set_memory_limit(&mut self, cells: usize) {
+        self.limit = cells;
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn rel_base(&self) -> i64 {
+        self.rel_base
+    }
+
+    /// Reads a cell, cells never written are zero.
+    pub fn peek(&self, addr: usize) -> i64 {
+        match self.memory.get(addr) {
+            Some(&value) => value,
+            None => self.far.get(&addr).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn into_memory(self) -> Image {
+        let mut image = Image::new(self.memory);
+        let mut far: Vec<_> = self.far.into_iter().collect();
+        far.sort_unstable();
+        far.into_iter().for_each(|(addr, value)| image.set(addr, value));
+        image
+    }
+
+    fn negative(&self, addr: i64) -> IntcodeError {
+        IntcodeError::NegativeAddress { pc: self.pc, raw: self.peek(self.pc), addr }
+    }
+
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow { pc: self.pc, raw: self.peek(self.pc) }
+    }
+
+    #[inline]
+    fn relative(&self, addr: i64) -> Result<i64, IntcodeError> {
+        addr.checked_add(self.rel_base).ok_or_else(|| self.overflow())
+    }
+
+    #[inline]
+    fn load(&self, addr: i64) -> Result<i64, IntcodeError> {
+        if addr < 0 {
+            return Err(self.negative(addr));
+        }
+        Ok(self.peek(addr as usize))
+    }
+
+    #[inline]
+    fn store(&mut self, addr: usize, value: i64) -> Result<(), IntcodeError> {
+        if addr >= self.limit {
+            let (pc, raw, limit) = (self.pc, self.peek(self.pc), self.limit);
+            return Err(IntcodeError::MemoryLimit { pc, raw, addr, limit });
+        }
+        if addr >= self.memory.len() {
+            if addr >= DENSE_CELLS {
+                self.far.insert(addr, value);
+                return Ok(());
+            }
+            self.memory.resize(addr + 1, 0);
+            self.decoded.resize(addr + 1, None);
+        }
+        self.memory[addr] = value;
+        self.decoded[addr] = None;
+        Ok(())
+    }
+
+    #[inline]
+    fn arg(&self, n: usize, mode: u8) -> Result<i64, IntcodeError> {
+        let raw = self.load((self.pc + n + 1) as i64)?;
+        match mode {
+            POSITION => self.load(raw),
+            RELATIVE => self.load(self.relative(raw)?),
+            _ => Ok(raw),
+        }
+    }
+
+    #[inline]
+    fn target(&self, n: usize, mode: u8) -> Result<usize, IntcodeError> {
+        let mut addr = self.load((self.pc + n + 1) as i64)?;
+        if mode == RELATIVE {
+            addr = self.relative(addr)?;
+        }
+        if addr < 0 {
+            return Err(self.negative(addr));
+        }
+        Ok(addr as usize)
+    }
+
+    #[inline]
+    fn jump(&mut self, addr: i64) -> Result<(), IntcodeError> {
+        if addr < 0 {
+            return Err(self.negative(addr));
+        }
+        self.pc = addr as usize;
+        Ok(())
+    }
+
+    fn decode(&mut self) -> Result<Decoded, IntcodeError> {
+        if let Some(Some(decoded)) = self.decoded.get(self.pc) {
+            return Ok(*decoded);
+        }
+        let decoded = Decoded::new(self.load(self.pc as i64)?, self.pc)?;
+        if self.pc < self.decoded.len() {
+            self.decoded[self.pc] = Some(decoded);
+        }
+        Ok(decoded)
+    }
+
+    pub fn step(&mut self) -> Result<Status, IntcodeError> {
+        let Decoded { op, modes } = self.decode()?;
+        match op {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = (self.arg(0, modes[0])?, self.arg(1, modes[1])?);
+                let res_addr = self.target(2, modes[2])?;
+                let value = match op {
+                    1 => a.checked_add(b).ok_or_else(|| self.overflow())?,
+                    2 => a.checked_mul(b).ok_or_else(|| self.overflow())?,
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+                self.store(res_addr, value)?;
+                self.pc += 4;
+            },
+            3 => {
+                // Like the interpreter, a bad target only fails once there's
+                // a value to write.
+                if self.inputs.is_empty() {
+                    return Ok(Status::NeedsInput);
+                }
+                let res_addr = self.target(0, modes[0])?;
+                let value = self.inputs.pop_front().unwrap();
+                self.store(res_addr, value)?;
+                self.pc += 2;
+            },
+            4 => {
+                let value = self.arg(0, modes[0])?;
+                self.pc += 2;
+                return Ok(Status::Output(value));
+            },
+            5 | 6 => {
+                let (test, addr) = (self.arg(0, modes[0])?, self.arg(1, modes[1])?);
+                if (test != 0) == (op == 5) {
+                    self.jump(addr)?;
+                } else {
+                    self.pc += 3;
+                }
+            },
+            9 => {
+                let offset = self.arg(0, modes[0])?;
+                self.rel_base = self.relative(offset)?;
+                self.pc += 2;
+            },
+            _ => return Ok(Status::Halted),
+        }
+        Ok(Status::Running)
+    }
+
+    pub fn run_until_io(&mut self) -> Result<Status, IntcodeError> {
+        loop {
+            match self.step()? {
+                Status::Running => continue,
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Runs on the queued input until halt. Returns the final memory and
+    /// all outputs, running out of input is reported as `InputClosed`.
+    pub fn run(mut self) -> Result<(Image, Vec<i64>), IntcodeError> {
+        let mut outputs = vec![];
+        loop {
+            match self.run_until_io()? {
+                Status::Output(v) => outputs.push(v),
+                Status::NeedsInput => {
+                    let (pc, raw) = (self.pc, self.peek(self.pc));
+                    return Err(IntcodeError::InputClosed { pc, raw });
+                },
+                _ => return Ok((self.into_memory(), outputs)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::intcode_pc::{load_prog, IntcodePC};
+
+    fn both(prog: &[i64], inputs: &[i64]) -> Vec<i64> {
+        let mut fast = FastPC::new(prog.to_vec());
+        let mut slow = IntcodePC::synchronous(prog.to_vec());
+        for &v in inputs {
+            fast.push_input(v);
+            slow.push_input(v);
+        }
+        let fast = fast.run().unwrap().1;
+        assert_eq!(fast, slow.run().unwrap().1);
+        fast
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(Decoded::new(1002, 0), Ok(Decoded { op: 2, modes: [0, 1, 0] }));
+        assert_eq!(Decoded::new(21107, 0), Ok(Decoded { op: 7, modes: [1, 1, 2] }));
+        assert_eq!(Decoded::new(99, 0), Ok(Decoded { op: 99, modes: [0, 0, 0] }));
+        assert_eq!(Decoded::new(199, 3), Err(IntcodeError::UnknownOpcode { pc: 3, raw: 199 }));
+        assert_eq!(Decoded::new(304, 0), Err(IntcodeError::InvalidMode { pc: 0, raw: 304, mode: 3 }));
+        assert_eq!(Decoded::new(10001, 0), Err(IntcodeError::ImmediateWrite { pc: 0, raw: 10001 }));
+        assert_eq!(Decoded::new(-1, 0), Err(IntcodeError::UnknownOpcode { pc: 0, raw: -1 }));
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        let day9 = load_prog(include_str!("../../day9/input"));
+        assert_eq!(both(&day9, &[1]).len(), 1);
+        assert_eq!(both(&day9, &[2]).len(), 1);
+        let day5 = load_prog(include_str!("../../day5/input"));
+        assert_eq!(both(&day5, &[1]).last(), Some(&9_775_037));
+        assert_eq!(both(&day5, &[5]), [15_586_959]);
+        let quine = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        assert_eq!(both(&quine, &[]), quine);
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        // The first add turns the already decoded mul into an add.
+        let prog = assemble("
+                    add  #1100, #1, [patched]
+            patched: mul  #20, #22, [result]
+                    out  [result]
+                    hlt
+            result: data 0
+        ").unwrap();
+        let mut pc = FastPC::new(prog);
+        pc.pc = 4;
+        assert_eq!(pc.decode(), Ok(Decoded { op: 2, modes: [1, 1, 0] }));
+        pc.pc = 0;
+        assert_eq!(pc.run().unwrap().1, [42]);
+    }
+
+    #[test]
+    fn grows_memory() {
+        let mut pc = FastPC::new(vec![1101, 2, 3, 100, 4, 100, 4, 1000, 99]);
+        assert_eq!(pc.run_until_io(), Ok(Status::Output(5)));
+        assert_eq!(pc.run_until_io(), Ok(Status::Output(0)));
+        assert_eq!(pc.memory.len(), 101);
+        assert_eq!(pc.run_until_io(), Ok(Status::Halted));
+
+        // Far writes stay sparse, and code there still runs.
+        let prog = vec![1101, 99, 0, 1_000_000_000, 1105, 1, 1_000_000_000];
+        let (memory, _) = FastPC::new(prog.clone()).run().unwrap();
+        assert_eq!(memory, IntcodePC::synchronous(prog).run().unwrap().0);
+        assert_eq!((memory.len(), memory.cells().count()), (1_000_000_001, 8));
+    }
+
+    #[test]
+    fn errors() {
+        let mut pc = FastPC::new(vec![1, -1, 0, 0]);
+        assert_eq!(pc.step(), Err(IntcodeError::NegativeAddress { pc: 0, raw: 1, addr: -1 }));
+        let pc = FastPC::new(vec![3, 0, 99]);
+        assert_eq!(pc.run().unwrap_err(), IntcodeError::InputClosed { pc: 0, raw: 3 });
+        let mut pc = FastPC::new(vec![203, -1, 99]);
+        assert_eq!(pc.step(), Ok(Status::NeedsInput));
+        pc.push_input(1);
+        assert_eq!(pc.step(), Err(IntcodeError::NegativeAddress { pc: 0, raw: 203, addr: -1 }));
+        let mut pc = FastPC::new(vec![1102, i64::MAX, 2, 0, 99]);
+        assert_eq!(pc.step(), Err(IntcodeError::Overflow { pc: 0, raw: 1102 }));
+        let mut pc = FastPC::new(vec![1101, 2, 3, 1_000_000_000, 99]);
+        pc.set_memory_limit(1024);
+        assert_eq!(pc.step(),
+            Err(IntcodeError::MemoryLimit { pc: 0, raw: 1101, addr: 1_000_000_000, limit: 1024 }));
+    }
+}
@@ -0,0 +1,400 @@
+//! Differential fuzzing of the engines in this crate on random programs.
+//! The old per-day interpreters are gone, `Dialect` only keeps their
+//! instruction sets. Their `i32` cells were intentionally left out: every
+//! engine here works on `i64` and fails on overflow, so a 32-bit dialect
+//! would only report the width difference.
+
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::error::IntcodeError;
+use crate::fast::FastPC;
+use crate::intcode_pc::{IntcodePC, Status};
+use crate::memory::Dense;
+use crate::symbolic::{SymbolicError, SymbolicPC};
+
+/// Writes past this fail the same way on every engine, so random programs
+/// can't allocate their way out of memory.
+const MEMORY_LIMIT: usize = 4096;
+
+const POSITION: i64 = 0;
+const IMMEDIATE: i64 = 1;
+const RELATIVE: i64 = 2;
+
+/// Instruction sets of the interpreters the day crates used to carry
+/// before they were merged into this crate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dialect {
+    /// `add`, `mul` and `hlt` in position mode.
+    Day2,
+    /// Everything but relative addressing, no `rbo`.
+    Day5,
+    Day9,
+}
+
+impl Dialect {
+    fn opcodes(self) -> &'static [i64] {
+        match self {
+            Dialect::Day2 => &[1, 2],
+            Dialect::Day5 => &[1, 2, 3, 4, 5, 6, 7, 8],
+            Dialect::Day9 => &[1, 2, 3, 4, 5, 6, 7, 8, 9],
+        }
+    }
+
+    fn modes(self) -> &'static [i64] {
+        match self {
+            Dialect::Day2 => &[POSITION],
+            Dialect::Day5 => &[POSITION, IMMEDIATE],
+            Dialect::Day9 => &[POSITION, IMMEDIATE, RELATIVE],
+        }
+    }
+}
+
+/// xorshift64*, good enough to pick instructions and reproducible from a
+/// seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniform in `low..=high`.
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + self.below((high - low + 1) as usize) as i64
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Case {
+    pub program: Vec<i64>,
+    pub inputs: Vec<i64>,
+}
+
+impl Case {
+    /// Shrinking has to make this smaller.
+    fn size(&self) -> (usize, i64) {
+        let cells = self.program.iter().chain(&self.inputs);
+        (self.program.len() + self.inputs.len(), cells.map(|v| v.saturating_abs()).fold(0, i64::saturating_add))
+    }
+}
+
+fn params(op: i64) -> usize {
+    match op {
+        1 | 2 | 7 | 8 => 3,
+        5 | 6 => 2,
+        _ => 1,
+    }
+}
+
+/// Random program of `instructions` instructions from `dialect`, followed
+/// by a `hlt` and a few data cells. Parameters mostly point at the data,
+/// sometimes at the code; jumps land on instruction boundaries.
+pub fn generate(rng: &mut Rng, dialect: Dialect, instructions: usize) -> Case {
+    let ops: Vec<i64> = (0..instructions).map(|_| rng.pick(dialect.opcodes())).collect();
+    let starts: Vec<i64> = ops.iter()
+        .scan(0, |addr, &op| {
+            let start = *addr;
+            *addr += params(op) as i64 + 1;
+            Some(start)
+        })
+        .collect();
+    let code = starts.last().map_or(0, |&s| s + params(*ops.last().unwrap()) as i64 + 1);
+    let targets: Vec<i64> = starts.iter().copied().chain(Some(code)).collect();
+    let len = code + 1 + 8;
+    let mut program = vec![];
+    for &op in ops.iter() {
+        let write = match op {
+            1 | 2 | 7 | 8 => Some(2),
+            3 => Some(0),
+            _ => None,
+        };
+        let (mut raw, mut args) = (op, vec![]);
+        for n in 0..params(op) {
+            let mut mode = rng.pick(dialect.modes());
+            if write == Some(n) && mode == IMMEDIATE {
+                mode = POSITION;
+            }
+            raw += mode * 10_i64.pow(n as u32 + 2);
+            args.push(match mode {
+                IMMEDIATE if (op == 5 || op == 6) && n == 1 => rng.pick(&targets),
+                IMMEDIATE if op == 9 => rng.range(-3, 3),
+                IMMEDIATE => rng.range(-10, 10),
+                RELATIVE => rng.range(-4, len),
+                _ if rng.below(4) == 0 => rng.range(0, len - 1),
+                _ => rng.range(code + 1, len - 1),
+            });
+        }
+        program.push(raw);
+        program.extend(args);
+    }
+    program.push(99);
+    program.extend((0..8).map(|_| rng.range(-10, 10)));
+    let inputs = match dialect {
+        Dialect::Day2 => vec![],
+        _ => (0..rng.below(4)).map(|_| rng.range(-10, 100)).collect(),
+    };
+    Case { program, inputs }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Stop {
+    Halted,
+    NeedsInput,
+    StepLimit,
+    Error(IntcodeError),
+    /// Engine specific failure with no counterpart on the others.
+    Unsupported(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Outcome {
+    pub stop: Stop,
+    pub outputs: Vec<i64>,
+    pub memory: Vec<i64>,
+}
+
+/// Runs a case for at most the given number of instructions.
+pub type Engine = fn(&Case, u64) -> Outcome;
+
+fn steps(max_steps: u64, mut step: impl FnMut() -> Result<Status, IntcodeError>, outputs: &mut Vec<i64>) -> Stop {
+    for _ in 0..max_steps {
+        match step() {
+            Ok(Status::Running) => (),
+            Ok(Status::Output(v)) => outputs.push(v),
+            Ok(Status::Halted) => return Stop::Halted,
+            Ok(Status::NeedsInput) => return Stop::NeedsInput,
+            Err(e) => return Stop::Error(e),
+        }
+    }
+    Stop::StepLimit
+}
+
+fn run_pc(mut pc: IntcodePC, case: &Case, max_steps: u64) -> Outcome {
+    pc.set_memory_limit(MEMORY_LIMIT);
+    case.inputs.iter().for_each(|&v| pc.push_input(v));
+    let mut outputs = vec![];
+    let stop = steps(max_steps, || pc.step(), &mut outputs);
+    // Dense is fine, writes stop at `MEMORY_LIMIT`.
+    Outcome { stop, outputs, memory: pc.memory().image().to_vec() }
+}
+
+fn interpreter(case: &Case, max_steps: u64) -> Outcome {
+    run_pc(IntcodePC::synchronous(case.program.clone()), case, max_steps)
+}
+
+fn dense(case: &Case, max_steps: u64) -> Outcome {
+    let mut pc = IntcodePC::synchronous(vec![]);
+    pc.set_memory(Box::new(Dense::new(case.program.clone())));
+    run_pc(pc, case, max_steps)
+}
+
+/// Profiling and self-modification detection take the traced path.
+fn observed(case: &Case, max_steps: u64) -> Outcome {
+    let mut pc = IntcodePC::synchronous(case.program.clone());
+    pc.enable_profiling();
+    pc.detect_self_modification(false);
+    run_pc(pc, case, max_steps)
+}
+
+fn fast(case: &Case, max_steps: u64) -> Outcome {
+    let mut pc = FastPC::new(case.program.clone());
+    pc.set_memory_limit(MEMORY_LIMIT);
+    case.inputs.iter().for_each(|&v| pc.push_input(v));
+    let mut outputs = vec![];
+    let stop = steps(max_steps, || pc.step(), &mut outputs);
+    Outcome { stop, outputs, memory: pc.into_memory().to_vec() }
+}
+
+fn symbolic(case: &Case, max_steps: u64) -> Outcome {
+    let mut pc = SymbolicPC::new(&case.program);
+    pc.set_memory_limit(MEMORY_LIMIT);
+    case.inputs.iter().for_each(|&v| pc.push_input(v));
+    let stop = match pc.run(max_steps) {
+        Ok(()) => Stop::Halted,
+        Err(SymbolicError::StepLimit) => Stop::StepLimit,
+        Err(SymbolicError::Machine(IntcodeError::InputClosed { .. })) => Stop::NeedsInput,
+        Err(SymbolicError::Machine(e)) => Stop::Error(e),
+        Err(e) => Stop::Unsupported(e.to_string()),
+    };
+    let concrete = |e: &crate::symbolic::Expr| e.as_const().expect("no symbols, no expressions");
+    let mut memory = vec![];
+    for (addr, e) in pc.cells() {
+        memory.resize(memory.len().max(addr + 1), 0);
+        memory[addr] = concrete(e);
+    }
+    Outcome { stop, outputs: pc.outputs().iter().map(concrete).collect(), memory }
+}
+
+/// Every engine in the crate, the first one is the reference.
+pub fn engines() -> Vec<(&'static str, Engine)> {
+    vec![
+        ("interpreter", interpreter as Engine),
+        ("dense", dense),
+        ("observed", observed),
+        ("fast", fast),
+        ("symbolic", symbolic),
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub case: Case,
+    pub outcomes: Vec<(&'static str, Outcome)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "program: {}", self.case.program.iter().join(","))?;
+        writeln!(f, "inputs:  {}", self.case.inputs.iter().join(","))?;
+        for (name, outcome) in self.outcomes.iter() {
+            writeln!(f, "{:>12}: {:?}, outputs [{}]", name, outcome.stop, outcome.outputs.iter().join(","))?;
+            writeln!(f, "{:>12}  memory [{}]", "", outcome.memory.iter().join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `case` on every engine, `Some` if any of them disagrees with the
+/// first one.
+pub fn check(case: &Case, engines: &[(&'static str, Engine)], max_steps: u64) -> Option<Divergence> {
+    let outcomes: Vec<_> = engines.iter().map(|&(name, engine)| (name, engine(case, max_steps))).collect();
+    if outcomes.iter().all(|(_, outcome)| *outcome == outcomes[0].1) {
+        return None;
+    }
+    Some(Divergence { case: case.clone(), outcomes })
+}
+
+/// Smaller variants of `case`: fewer cells or inputs, or values closer to
+/// zero. Removing a cell moves everything after it, which is fine as long
+/// as the result still diverges.
+fn shrinks(case: &Case) -> Vec<Case> {
+    let mut shrinks = vec![];
+    for n in 0..case.inputs.len() {
+        let mut shrunk = case.clone();
+        shrunk.inputs.remove(n);
+        shrinks.push(shrunk);
+    }
+    let mut half = case.clone();
+    half.program.truncate(case.program.len() / 2);
+    shrinks.push(half);
+    for width in (1..=4).rev() {
+        for addr in (0..case.program.len().saturating_sub(width - 1)).rev() {
+            let mut shrunk = case.clone();
+            shrunk.program.drain(addr..addr + width);
+            shrinks.push(shrunk);
+        }
+    }
+    let simpler = |v: i64| vec![0, 99, v / 2].into_iter().filter(move |s| s.abs() < v.abs());
+    for (addr, &v) in case.program.iter().enumerate() {
+        for s in simpler(v) {
+            let mut shrunk = case.clone();
+            shrunk.program[addr] = s;
+            shrinks.push(shrunk);
+        }
+    }
+    for (n, &v) in case.inputs.iter().enumerate() {
+        for s in simpler(v) {
+            let mut shrunk = case.clone();
+            shrunk.inputs[n] = s;
+            shrinks.push(shrunk);
+        }
+    }
+    shrinks.retain(|shrunk| shrunk.size() < case.size());
+    shrinks
+}
+
+/// Shrinks a diverging case for as long as it keeps diverging.
+pub fn minimize(divergence: Divergence, engines: &[(&'static str, Engine)], max_steps: u64) -> Divergence {
+    let mut best = divergence;
+    while let Some(smaller) = shrinks(&best.case).iter().find_map(|case| check(case, engines, max_steps)) {
+        best = smaller;
+    }
+    best
+}
+
+/// Tries `cases` random programs of up to 12 instructions and returns the
+/// first divergence, minimized.
+pub fn fuzz(
+    seed: u64,
+    cases: usize,
+    dialect: Dialect,
+    engines: &[(&'static str, Engine)],
+    max_steps: u64,
+) -> Option<Divergence>
+{
+    let mut rng = Rng::new(seed);
+    (0..cases)
+        .map(|_| {
+            let instructions = rng.range(1, 12) as usize;
+            generate(&mut rng, dialect, instructions)
+        })
+        .find_map(|case| check(&case, engines, max_steps))
+        .map(|divergence| minimize(divergence, engines, max_steps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_programs() {
+        let mut rng = Rng::new(7);
+        for dialect in [Dialect::Day2, Dialect::Day5, Dialect::Day9].iter() {
+            for _ in 0..50 {
+                let case = generate(&mut rng, *dialect, 6);
+                let mut pc = 0;
+                for _ in 0..6 {
+                    let raw = case.program[pc];
+                    assert!(dialect.opcodes().contains(&(raw % 100)), "{:?} {:?}", dialect, case);
+                    assert!((0..params(raw % 100)).all(|n| dialect.modes().contains(&(raw / 10_i64.pow(n as u32 + 2) % 10))));
+                    pc += params(raw % 100) + 1;
+                }
+                assert_eq!(case.program[pc], 99);
+            }
+        }
+        assert!(generate(&mut rng, Dialect::Day2, 3).inputs.is_empty());
+    }
+
+    #[test]
+    fn engines_agree() {
+        for (seed, dialect) in [(1, Dialect::Day2), (2, Dialect::Day5), (3, Dialect::Day9)].iter() {
+            if let Some(divergence) = fuzz(*seed, 300, *dialect, &engines(), 500) {
+                panic!("{:?} diverged:\n{}", dialect, divergence);
+            }
+        }
+    }
+
+    #[test]
+    fn minimizes_divergence() {
+        fn drops_negatives(case: &Case, max_steps: u64) -> Outcome {
+            let mut outcome = interpreter(case, max_steps);
+            outcome.outputs.retain(|&v| v >= 0);
+            outcome
+        }
+        let engines = [("interpreter", interpreter as Engine), ("broken", drops_negatives)];
+        let case = Case { program: vec![1102, 0, 0, 0, 104, -5, 99, 1, 2, 3], inputs: vec![4] };
+        let divergence = minimize(check(&case, &engines, 100).unwrap(), &engines, 100);
+        assert_eq!(divergence.case, Case { program: vec![104, -1], inputs: vec![] });
+        assert!(divergence.to_string().starts_with("program: 104,-1\n"));
+
+        let divergence = fuzz(5, 1000, Dialect::Day5, &engines, 500).unwrap();
+        assert!(divergence.outcomes[0].1.outputs.iter().any(|&v| v < 0));
+    }
+}
@@ -0,0 +1,929 @@
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread::{self, JoinHandle};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use crate::disasm;
+use crate::error::IntcodeError;
+use crate::io::IntcodeIo;
+use crate::limits::CancelHandle;
+use crate::memory::{Image, Memory, Paged, Ram};
+use crate::profile::Profile;
+use crate::smc::{SelfModification, SmcDetector, SmcHook, SmcStats};
+use crate::snapshot::Snapshot;
+use crate::trace::TraceEvent;
+
+pub fn load_prog(input: &str) -> Vec<i64> {
+    input.split(",").map(|s| {
+        s.trim().parse::<i64>().unwrap()
+    }).collect()
+}
+
+/// `load_prog` for text that may not be a program, reports the first value
+/// that isn't a number instead of panicking.
+pub fn parse_prog(input: &str) -> Result<Vec<i64>, String> {
+    input.split(',').enumerate()
+        .map(|(n, s)| s.trim().parse().map_err(|_| format!("invalid value {:?} at position {}", s.trim(), n)))
+        .collect()
+}
+
+/// Reads and parses the program in the file at `path`.
+pub fn read_prog(path: impl AsRef<Path>) -> Result<Vec<i64>, String> {
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_prog(&source)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Mode {
+    Position,
+    Immediate,
+    Relative,
+    ReturnAddr,
+    ReturnAddrRelative,
+}
+
+impl Mode {
+    fn to_return_mode(self, pc: usize, raw: i64) -> Result<Self, IntcodeError> {
+        match self {
+            Mode::Position => Ok(Mode::ReturnAddr),
+            Mode::Relative => Ok(Mode::ReturnAddrRelative),
+            Mode::Immediate => Err(IntcodeError::ImmediateWrite { pc, raw }),
+            _ => Ok(self),
+        }
+    }
+}
+
+#[allow(clippy::wrong_self_convention)]
+trait AsMode {
+    fn as_mode(self, pc: usize, raw: i64) -> Result<Mode, IntcodeError>;
+}
+
+impl AsMode for u32 {
+    fn as_mode(self, pc: usize, raw: i64) -> Result<Mode, IntcodeError> {
+        match self {
+            0 => Ok(Mode::Position),
+            1 => Ok(Mode::Immediate),
+            2 => Ok(Mode::Relative),
+            mode => Err(IntcodeError::InvalidMode { pc, raw, mode }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Message {
+    Value(i64),
+    NeedInput,
+    Finished,
+}
+
+impl Message {
+    pub fn unwrap_val(self) -> i64 {
+        if let Message::Value(v) = self {
+            v
+        } else {
+            panic!("Failed on value unwrapping")
+        }
+    }
+}
+
+/// What the machine is waiting on after a `step` or `run_until_io`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    Running,
+    Halted,
+    NeedsInput,
+    Output(i64),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Order {
+    Add { a: Mode, b: Mode, res_addr: Mode },
+    Mul { a: Mode, b: Mode, res_addr: Mode },
+    In  { res_addr: Mode },
+    Out { val: Mode },
+    Jit { test: Mode, addr: Mode },
+    Jif { test: Mode, addr: Mode },
+    Lt  { a: Mode, b: Mode, res_addr: Mode },
+    Eq  { a: Mode, b: Mode, res_addr: Mode },
+    Rbo { offset: Mode },
+}
+
+#[allow(clippy::wrong_self_convention)]
+trait AsOrder {
+    fn as_order(self, params: [Mode; 3], pc: usize) -> Result<Order, IntcodeError>;
+}
+
+impl AsOrder for i64 {
+    fn as_order(self, params: [Mode; 3], pc: usize) -> Result<Order, IntcodeError> {
+        use Order::*;
+        let ret = |mode: Mode| mode.to_return_mode(pc, self);
+        Ok(match self%100 {
+            1 => Add { a: params[0], b: params[1], res_addr: ret(params[2])? },
+            2 => Mul { a: params[0], b: params[1], res_addr: ret(params[2])? },
+            3 => In  { res_addr: ret(params[0])? },
+            4 => Out { val: params[0] },
+            5 => Jit { test: params[0], addr: params[1] },
+            6 => Jif { test: params[0], addr: params[1] },
+            7 => Lt  { a: params[0], b: params[1], res_addr: ret(params[2])? },
+            8 => Eq  { a: params[0], b: params[1], res_addr: ret(params[2])? },
+            9 => Rbo { offset: params[0] },
+            _ => return Err(IntcodeError::UnknownOpcode { pc, raw: self }),
+        })
+    }
+}
+
+impl Order {
+    pub(crate) fn new(raw: i64, pc: usize) -> Result<Self, IntcodeError> {
+        if !(0..100_000).contains(&raw) {
+            return Err(IntcodeError::UnknownOpcode { pc, raw });
+        }
+        let digits = raw.to_string();
+        let mut params = digits.chars().rev().skip(2).map(|c| c.to_digit(10).unwrap());
+        let params = [params.next().unwrap_or(0).as_mode(pc, raw)?,
+                      params.next().unwrap_or(0).as_mode(pc, raw)?,
+                      params.next().unwrap_or(0).as_mode(pc, raw)?];
+        raw.as_order(params, pc)
+    }
+
+    pub(crate) fn modes(&self) -> Vec<Mode> {
+        use Order::*;
+        match *self {
+            Add { a, b, res_addr } => vec![a, b, res_addr],
+            Mul { a, b, res_addr } => vec![a, b, res_addr],
+            In  { res_addr }       => vec![res_addr],
+            Out { val }            => vec![val],
+            Jit { test, addr }     => vec![test, addr],
+            Jif { test, addr }     => vec![test, addr],
+            Lt  { a, b, res_addr } => vec![a, b, res_addr],
+            Eq  { a, b, res_addr } => vec![a, b, res_addr],
+            Rbo { offset }         => vec![offset],
+        }
+    }
+
+    /// Index of the parameter the order writes to.
+    fn write_param(&self) -> Option<usize> {
+        use Order::*;
+        match *self {
+            Add {..} | Mul {..} | Lt {..} | Eq {..} => Some(2),
+            In {..} => Some(0),
+            _ => None,
+        }
+    }
+
+    /// Addresses the order reads its arguments from.
+    fn read_addrs(&self, memory: &Ram, pc: usize, rel_base: i64) -> Vec<usize> {
+        self.modes().into_iter()
+            .enumerate()
+            .filter_map(|(n, mode)| {
+                let arg = memory.cells.get(pc + n + 1);
+                match mode {
+                    Mode::Position => Some(arg),
+                    Mode::Relative => arg.checked_add(rel_base),
+                    _ => None,
+                }
+            })
+            .filter_map(|addr| addr.try_into().ok())
+            .collect()
+    }
+
+    fn parse_args(&self, memory: &Ram, pc: &usize, rel_base: &i64) -> Result<Vec<i64>, IntcodeError> {
+        let raw = memory.cells.get(*pc);
+        let relative = |arg: i64| arg.checked_add(*rel_base).ok_or(IntcodeError::Overflow { pc: *pc, raw });
+        self.modes().into_iter()
+            .enumerate()
+            .map(|(n, mode)| {
+                let read = |addr| memory.read(addr).map_err(|fault| fault.at(*pc, raw));
+                let arg = read((*pc+n+1) as i64)?;
+                Ok(match mode {
+                    Mode::Position           => read(arg)?,
+                    Mode::Relative           => read(relative(arg)?)?,
+                    Mode::Immediate          => arg,
+                    Mode::ReturnAddr         => arg,
+                    Mode::ReturnAddrRelative => relative(arg)?,
+                })
+            })
+            .collect()
+    }
+
+    fn jump(pc: &mut usize, addr: i64) -> Option<()> {
+        *pc = addr.try_into().ok()?;
+        Some(())
+    }
+
+    fn execute(
+        self,
+        memory: &mut Ram,
+        pc: &mut usize,
+        inputs: &mut VecDeque<i64>,
+        rel_base: &mut i64
+    ) -> Result<Status, IntcodeError>
+    {
+        use Order::*;
+        let (start, raw) = (*pc, memory.cells.get(*pc));
+        let negative = |addr| IntcodeError::NegativeAddress { pc: start, raw, addr };
+        let overflow = IntcodeError::Overflow { pc: start, raw };
+        let args = self.parse_args(memory, pc, rel_base)?;
+        let mut write = |addr, value| memory.write(addr, value).map_err(|fault| fault.at(start, raw));
+        match self {
+            Add {..} => {
+                write(args[2], args[0].checked_add(args[1]).ok_or(overflow)?)?;
+                *pc += 4;
+            },
+            Mul {..} => {
+                write(args[2], args[0].checked_mul(args[1]).ok_or(overflow)?)?;
+                *pc += 4;
+            },
+            In  {..} => {
+                if let Some(val) = inputs.pop_front() {
+                    write(args[0], val)?;
+                    *pc += 2;
+                } else {
+                    return Ok(Status::NeedsInput);
+                }
+            },
+            Out {..} => {
+                *pc += 2;
+                return Ok(Status::Output(args[0]));
+            },
+            Jit {..} => {
+                if args[0] != 0 {
+                    Self::jump(pc, args[1]).ok_or_else(|| negative(args[1]))?;
+                } else {
+                    *pc += 3;
+                }
+            },
+            Jif {..} => {
+                if args[0] == 0 {
+                    Self::jump(pc, args[1]).ok_or_else(|| negative(args[1]))?;
+                } else {
+                    *pc += 3;
+                }
+            },
+            Lt  {..} => {
+                write(args[2], if args[0] < args[1] { 1 } else { 0 })?;
+                *pc += 4;
+            },
+            Eq  {..} => {
+                write(args[2], if args[0] == args[1] { 1 } else { 0 })?;
+                *pc += 4;
+            },
+            Rbo {..} => {
+                *rel_base = rel_base.checked_add(args[0]).ok_or(overflow)?;
+                *pc += 2;
+            },
+        };
+        Ok(Status::Running)
+    }
+}
+
+pub type Tracer = Box<dyn FnMut(&TraceEvent) + Send>;
+
+/// Machine over `i64` cells. Arithmetic is checked: a result that doesn't
+/// fit fails with `IntcodeError::Overflow` instead of wrapping.
+pub struct IntcodePC {
+    memory: Ram,
+    pc: usize,
+    rel_base: i64,
+    inputs: VecDeque<i64>,
+    tracer: Option<Tracer>,
+    profile: Option<Profile>,
+    smc: Option<SmcDetector>,
+    cancel: CancelHandle,
+    notify_input: bool,
+    pub input: Option<Receiver<Message>>,
+    pub output: Option<Sender<Message>>,
+}
+
+impl IntcodePC {
+    pub fn new(program: Vec<i64>) -> (Self, Sender<Message>, Receiver<Message>) {
+        let (in_sender, in_receiver) = channel();
+        let (out_sender, out_receiver) = channel();
+        let mut pc = Self::synchronous(program);
+        pc.input = Some(in_receiver);
+        pc.output = Some(out_sender);
+        (pc, in_sender, out_receiver)
+    }
+
+    /// Machine fed by another machine's output, so it doesn't announce
+    /// `Message::NeedInput` downstream.
+    pub fn piped(program: Vec<i64>, input: Receiver<Message>) -> (Self, Receiver<Message>) {
+        let (out_sender, out_receiver) = channel();
+        let mut pc = Self::synchronous(program);
+        pc.notify_input = false;
+        pc.input = Some(input);
+        pc.output = Some(out_sender);
+        (pc, out_receiver)
+    }
+
+    /// Machine without channels, driven from the current thread with
+    /// `push_input` and `run_until_io`.
+    pub fn synchronous(program: Vec<i64>) -> Self {
+        Self {
+            memory: Ram::new(Box::new(Paged::new(program))),
+            pc: 0,
+            rel_base: 0,
+            inputs: VecDeque::new(),
+            tracer: None,
+            profile: None,
+            smc: None,
+            cancel: CancelHandle::default(),
+            notify_input: true,
+            input: None,
+            output: None,
+        }
+    }
+
+    /// Synchronous machine continuing from a previously taken snapshot. It
+    /// starts with default settings: `memory::Paged` storage, no memory
+    /// limit, tracer, profiler or self-modification detector.
+    pub fn restore(snapshot: Snapshot) -> Self {
+        let mut pc = Self::synchronous(vec![]);
+        pc.memory.cells = Box::new(Paged::from(snapshot.memory));
+        pc.pc = snapshot.pc;
+        pc.rel_base = snapshot.rel_base;
+        pc.inputs = snapshot.inputs;
+        pc
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.cells.image(),
+            pc: self.pc,
+            rel_base: self.rel_base,
+            inputs: self.inputs.clone(),
+        }
+    }
+
+    /// Independent synchronous copy of the machine, keeping the memory limit.
+    /// Channels, the tracer, profiler and self-modification detector stay with
+    /// the original, and the copy stores its memory in `memory::Paged`.
+    pub fn fork(&self) -> Self {
+        let mut fork = Self::restore(self.snapshot());
+        fork.memory.limit = self.memory.limit;
+        fork
+    }
+
+    /// Swaps the storage, e.g. for `memory::Dense`. Defaults to `memory::Paged`
+    /// holding the program.
+    pub fn set_memory(&mut self, memory: Box<dyn Memory>) {
+        self.memory.cells = memory;
+    }
+
+    /// Writes at or past `cells` fail with `IntcodeError::MemoryLimit`.
+    pub fn set_memory_limit(&mut self, cells: usize) {
+        self.memory.limit = Some(cells);
+    }
+
+    pub(crate) fn replace_memory_limit(&mut self, limit: Option<usize>) -> Option<usize> {
+        std::mem::replace(&mut self.memory.limit, limit)
+    }
+
+    /// Every executed instruction is passed to `tracer`, see `trace::json_lines`.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Starts collecting a `Profile` of everything executed from now on.
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_with(Profile::default);
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Watches for writes to addresses already executed as instructions.
+    /// In strict mode such a write fails with `IntcodeError::SelfModifying`
+    /// and leaves memory untouched.
+    pub fn detect_self_modification(&mut self, strict: bool) {
+        match self.smc.as_mut() {
+            Some(smc) => smc.strict = strict,
+            None => self.smc = Some(SmcDetector::new(strict)),
+        }
+    }
+
+    /// Enables detection if needed and reports every self-modifying write to `hook`.
+    pub fn on_self_modification(&mut self, hook: SmcHook) {
+        self.smc.get_or_insert_with(|| SmcDetector::new(false)).hook = Some(hook);
+    }
+
+    pub fn self_modification_stats(&self) -> Option<&SmcStats> {
+        self.smc.as_ref().map(|smc| &smc.stats)
+    }
+
+    /// Handle stopping `run_limited` from another thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn pending_input(&self) -> &VecDeque<i64> {
+        &self.inputs
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn rel_base(&self) -> i64 {
+        self.rel_base
+    }
+
+    pub fn memory(&self) -> &dyn Memory {
+        self.memory.cells.as_ref()
+    }
+
+    /// Reads a cell, cells past the end of memory are zero.
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.memory.cells.get(addr)
+    }
+
+    /// Writes a cell, ignoring the memory limit.
+    pub fn poke(&mut self, addr: usize, value: i64) {
+        self.memory.cells.set(addr, value);
+    }
+
+    pub fn into_memory(self) -> Image {
+        self.memory.cells.image()
+    }
+
+    fn current_instruction(&self) -> i64 {
+        self.memory.cells.get(self.pc)
+    }
+
+    fn read_addrs(&self) -> Vec<usize> {
+        Order::new(self.current_instruction(), self.pc)
+            .map(|order| order.read_addrs(&self.memory, self.pc, self.rel_base))
+            .unwrap_or_default()
+    }
+
+    /// Executes a single instruction. `NeedsInput` leaves the machine on the
+    /// `In` instruction, so it can be stepped again after `push_input`.
+    pub fn step(&mut self) -> Result<Status, IntcodeError> {
+        if self.tracer.is_some() || self.profile.is_some() || self.smc.is_some() {
+            let reads = if self.profile.is_some() { self.read_addrs() } else { vec![] };
+            let (status, event) = self.step_traced()?;
+            if let Some(event) = event {
+                if let Some(profile) = self.profile.as_mut() {
+                    profile.record(&event, &reads, self.pc);
+                }
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer(&event);
+                }
+            }
+            return Ok(status);
+        }
+        let raw = self.current_instruction();
+        if raw == 99 {
+            return Ok(Status::Halted);
+        }
+        let order = Order::new(raw, self.pc)?;
+        order.execute(&mut self.memory, &mut self.pc, &mut self.inputs, &mut self.rel_base)
+    }
+
+    /// Like `step`, but also describes the executed instruction. There's no
+    /// event when the machine is still waiting for input.
+    pub fn step_traced(&mut self) -> Result<(Status, Option<TraceEvent>), IntcodeError> {
+        let (pc, raw) = (self.pc, self.current_instruction());
+        let mut event = TraceEvent { pc, raw, op: "hlt".to_owned(), ..TraceEvent::default() };
+        if raw == 99 {
+            return Ok((Status::Halted, Some(event)));
+        }
+        let order = Order::new(raw, pc)?;
+        event.op = disasm::mnemonic(&order).to_owned();
+        event.args = order.parse_args(&self.memory, &pc, &self.rel_base)?;
+        let target = order.write_param().map(|n| event.args[n] as usize);
+        let is_input = matches!(order, Order::In {..});
+        let mut modified = None;
+        if let Some(smc) = self.smc.as_mut() {
+            smc.executing(pc, event.args.len() + 1);
+            let writes = !(is_input && self.inputs.is_empty());
+            if let Some(addr) = target.filter(|&addr| writes && smc.is_executed(addr)) {
+                if smc.strict {
+                    return Err(IntcodeError::SelfModifying { pc, raw, addr });
+                }
+                modified = Some((addr, self.memory.cells.get(addr)));
+            }
+        }
+        let status = order.execute(&mut self.memory, &mut self.pc, &mut self.inputs, &mut self.rel_base)?;
+        match status {
+            Status::NeedsInput => return Ok((status, None)),
+            Status::Output(v) => event.output = Some(v),
+            _ => (),
+        }
+        event.write = target.map(|addr| (addr, self.peek(addr)));
+        if let Some((addr, old)) = modified {
+            let new = self.peek(addr);
+            if let Some(smc) = self.smc.as_mut() {
+                smc.record(SelfModification { pc, addr, old, new });
+            }
+        }
+        if is_input {
+            event.input = event.write.map(|(_, v)| v);
+        }
+        Ok((status, Some(event)))
+    }
+
+    pub fn run_until_io(&mut self) -> Result<Status, IntcodeError> {
+        loop {
+            match self.step()? {
+                Status::Running => continue,
+                status => break Ok(status),
+            }
+        }
+    }
+
+    /// Runs against a device until the machine halts, or pauses on an `in`
+    /// the device has no value for.
+    pub fn run_io(&mut self, mut io: impl IntcodeIo) -> Result<Status, IntcodeError> {
+        loop {
+            match self.run_until_io()? {
+                Status::Output(v) => io.output(v),
+                Status::NeedsInput => match io.input() {
+                    Some(v) => self.push_input(v),
+                    None => return Ok(Status::NeedsInput),
+                },
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Runs the machine with `io` on its own thread, handing the device back
+    /// once it halts or runs out of input.
+    pub fn spawn<D>(mut self, mut io: D) -> JoinHandle<Result<D, IntcodeError>>
+    where
+        D: IntcodeIo + Send + 'static,
+    {
+        thread::spawn(move || self.run_io(&mut io).map(|_| io))
+    }
+
+    pub fn run(mut self) -> Result<(Image, Vec<i64>), IntcodeError> {
+        let mut outputs = vec![];
+        loop {
+            match self.run_until_io()? {
+                Status::Output(v) => {
+                    if let Some(output) = &self.output {
+                        let _ = output.send(Message::Value(v));
+                    }
+                    outputs.push(v);
+                },
+                Status::NeedsInput => {
+                    let (pc, raw) = (self.pc, self.current_instruction());
+                    let closed = IntcodeError::InputClosed { pc, raw };
+                    let input = self.input.as_ref().ok_or(closed)?;
+                    if self.notify_input {
+                        if let Some(output) = &self.output {
+                            output.send(Message::NeedInput).map_err(|_| closed)?;
+                        }
+                    }
+                    match input.recv().map_err(|_| closed)? {
+                        Message::Value(val) => self.push_input(val),
+                        _ => return Err(IntcodeError::UnexpectedMessage { pc, raw }),
+                    }
+                },
+                Status::Halted => break,
+                Status::Running => unreachable!(),
+            }
+        }
+        if let Some(output) = &self.output {
+            let _ = output.send(Message::Finished);
+        }
+        Ok((self.memory.cells.image(), outputs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use super::{*, Mode::*};
+    use crate::memory::Dense;
+    use crate::asm::assemble;
+
+    fn recv_value(output: &Receiver<Message>) -> i64 {
+        loop {
+            match output.recv().unwrap() {
+                Message::NeedInput => continue,
+                msg => break msg.unwrap_val(),
+            }
+        }
+    }
+
+    fn run_with_input(prog: Vec<i64>, value: i64) -> i64 {
+        let (intcode_pc, input, output) = IntcodePC::new(prog);
+        thread::spawn(move || intcode_pc.run());
+        input.send(Message::Value(value)).unwrap();
+        recv_value(&output)
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(parse_prog("1, 0,-2\n"), Ok(vec![1, 0, -2]));
+        assert_eq!(parse_prog("1,x,99"), Err("invalid value \"x\" at position 1".to_owned()));
+        assert!(parse_prog("  \n").is_err());
+        assert!(read_prog("/nonexistent/program").is_err());
+    }
+
+    #[test]
+    fn order_parse() {
+        let code = 1102;
+        assert_eq!(Order::new(code, 0),
+             Ok(Order::Mul { a: Immediate, b: Immediate, res_addr: ReturnAddr }));
+    }
+
+    #[test]
+    fn order_parse_default_params() {
+        let code = 1001;
+        assert_eq!(Order::new(code, 0),
+             Ok(Order::Add { a: Position, b: Immediate, res_addr: ReturnAddr }));
+    }
+
+    #[test]
+    fn order_parse_relative() {
+        let code = 21201;
+        assert_eq!(Order::new(code, 0),
+             Ok(Order::Add { a: Relative, b: Immediate, res_addr: ReturnAddrRelative }));
+    }
+
+    #[test]
+    fn input_output() {
+        let prog = vec![3,0,4,0,99];
+        let (intcode_pc, input, output) = IntcodePC::new(prog);
+        thread::spawn(move || intcode_pc.run());
+        assert_eq!(output.recv().unwrap(), Message::NeedInput);
+        input.send(Message::Value(15)).unwrap();
+        assert_eq!(output.recv().unwrap(), Message::Value(15));
+        assert_eq!(output.recv().unwrap(), Message::Finished);
+    }
+
+    #[test]
+    fn piped_does_not_notify() {
+        let (in_sender, in_receiver) = channel();
+        let (intcode_pc, output) = IntcodePC::piped(vec![3,0,4,0,99], in_receiver);
+        in_sender.send(Message::Value(7)).unwrap();
+        assert_eq!(intcode_pc.run().unwrap().1, [7]);
+        assert_eq!(output.recv().unwrap(), Message::Value(7));
+        assert_eq!(output.recv().unwrap(), Message::Finished);
+    }
+
+    #[test]
+    fn synchronous_input_output() {
+        let mut intcode_pc = IntcodePC::synchronous(vec![3,0,4,0,99]);
+        assert_eq!(intcode_pc.run_until_io(), Ok(Status::NeedsInput));
+        assert_eq!(intcode_pc.run_until_io(), Ok(Status::NeedsInput));
+        intcode_pc.push_input(15);
+        assert_eq!(intcode_pc.run_until_io(), Ok(Status::Output(15)));
+        assert_eq!(intcode_pc.run_until_io(), Ok(Status::Halted));
+        assert_eq!(intcode_pc.memory().image().to_vec(), [15,0,4,0,99]);
+    }
+
+    #[test]
+    fn synchronous_step() {
+        let mut intcode_pc = IntcodePC::synchronous(vec![1101,2,3,0,104,7,99]);
+        assert_eq!(intcode_pc.step(), Ok(Status::Running));
+        assert_eq!(intcode_pc.memory().get(0), 5);
+        assert_eq!(intcode_pc.step(), Ok(Status::Output(7)));
+        assert_eq!(intcode_pc.step(), Ok(Status::Halted));
+        assert_eq!(intcode_pc.step(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn synchronous_run_with_queued_input() {
+        let mut intcode_pc = IntcodePC::synchronous(vec![3,9,8,9,10,9,4,9,99,-1,8]);
+        intcode_pc.push_input(8);
+        assert_eq!(intcode_pc.run().unwrap().1, [1]);
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let intcode_pc = IntcodePC::synchronous(vec![1,0,0,0,42,99]);
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::UnknownOpcode { pc: 4, raw: 42 }));
+    }
+
+    #[test]
+    fn invalid_mode() {
+        let intcode_pc = IntcodePC::synchronous(vec![1301,0,0,0,99]);
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::InvalidMode { pc: 0, raw: 1301, mode: 3 }));
+    }
+
+    #[test]
+    fn immediate_write() {
+        let intcode_pc = IntcodePC::synchronous(vec![11101,0,0,0,99]);
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::ImmediateWrite { pc: 0, raw: 11101 }));
+    }
+
+    #[test]
+    fn negative_address() {
+        let intcode_pc = IntcodePC::synchronous(vec![1101,1,1,5,1,0,-3,0,99]);
+        assert_eq!(intcode_pc.run(),
+            Err(IntcodeError::NegativeAddress { pc: 4, raw: 1, addr: -3 }));
+
+        let intcode_pc = IntcodePC::synchronous(vec![109,-10,1201,0,0,0,99]);
+        assert_eq!(intcode_pc.run(),
+            Err(IntcodeError::NegativeAddress { pc: 2, raw: 1201, addr: -10 }));
+
+        let intcode_pc = IntcodePC::synchronous(vec![1105,1,-1,99]);
+        assert_eq!(intcode_pc.run(),
+            Err(IntcodeError::NegativeAddress { pc: 0, raw: 1105, addr: -1 }));
+    }
+
+    #[test]
+    fn memory_boundaries() {
+        // Reads and writes exactly one past the program.
+        let intcode_pc = IntcodePC::synchronous(vec![1001,5,1,5,99]);
+        assert_eq!(intcode_pc.run().unwrap().0.to_vec(), [1001,5,1,5,99,1]);
+
+        let mut intcode_pc = IntcodePC::synchronous(vec![1101,2,3,1_000_000_000,4,1_000_000_000,99]);
+        assert_eq!(intcode_pc.run_until_io(), Ok(Status::Output(5)));
+        assert_eq!(intcode_pc.memory().len(), 1_000_000_001);
+
+        // Finishing the run doesn't spell out the gap below the write.
+        let intcode_pc = IntcodePC::synchronous(vec![1101,2,3,1_000_000_000,4,1_000_000_000,99]);
+        let (memory, outputs) = intcode_pc.run().unwrap();
+        assert_eq!(outputs, [5]);
+        assert_eq!((memory.len(), memory[1_000_000_000], memory[999_999_999]), (1_000_000_001, 5, 0));
+        assert_eq!(memory.cells().count(), 8);
+        let mut intcode_pc = IntcodePC::synchronous(vec![1101,2,3,1_000_000_000,99]);
+        assert_eq!(intcode_pc.run_until_io(), Ok(Status::Halted));
+        let restored = IntcodePC::restore(intcode_pc.snapshot());
+        assert_eq!(restored.peek(1_000_000_000), 5);
+        assert_eq!(restored.into_memory().cells().count(), 6);
+    }
+
+    #[test]
+    fn overflow() {
+        let intcode_pc = IntcodePC::synchronous(vec![1101,i64::MAX,0,7,4,7,99]);
+        assert_eq!(intcode_pc.run().unwrap().1, [i64::MAX]);
+
+        let intcode_pc = IntcodePC::synchronous(vec![1101,i64::MAX,1,5,99,0]);
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::Overflow { pc: 0, raw: 1101 }));
+
+        let intcode_pc = IntcodePC::synchronous(vec![1101,0,0,0,1002,8,-1,0,i64::MIN,99]);
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::Overflow { pc: 4, raw: 1002 }));
+
+        let intcode_pc = IntcodePC::synchronous(vec![109,i64::MAX,109,1,99]);
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::Overflow { pc: 2, raw: 109 }));
+    }
+
+    #[test]
+    fn memory_limit() {
+        let mut intcode_pc = IntcodePC::synchronous(vec![1101,2,3,1_000_000_000,99]);
+        intcode_pc.set_memory_limit(1024);
+        assert_eq!(intcode_pc.run(),
+            Err(IntcodeError::MemoryLimit { pc: 0, raw: 1101, addr: 1_000_000_000, limit: 1024 }));
+
+        let mut intcode_pc = IntcodePC::synchronous(vec![]);
+        intcode_pc.set_memory(Box::new(Dense::new(vec![1101,2,3,7,4,7,99])));
+        intcode_pc.set_memory_limit(8);
+        let (memory, outputs) = intcode_pc.run().unwrap();
+        assert_eq!((memory.to_vec(), outputs), (vec![1101,2,3,7,4,7,99,5], vec![5]));
+    }
+
+    #[test]
+    fn unexpected_message() {
+        let (intcode_pc, input, _output) = IntcodePC::new(vec![3,0,99]);
+        input.send(Message::Finished).unwrap();
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::UnexpectedMessage { pc: 0, raw: 3 }));
+    }
+
+    #[test]
+    fn input_closed() {
+        let (intcode_pc, input, _output) = IntcodePC::new(vec![3,0,99]);
+        drop(input);
+        assert_eq!(intcode_pc.run(), Err(IntcodeError::InputClosed { pc: 0, raw: 3 }));
+        assert_eq!(IntcodePC::synchronous(vec![3,0,99]).run(),
+            Err(IntcodeError::InputClosed { pc: 0, raw: 3 }));
+    }
+
+    #[test]
+    fn first_prog() {
+        let prog = vec![1,0,0,0,99];
+        let (intcode_pc, _, _) = IntcodePC::new(prog);
+        assert_eq!(intcode_pc.run().unwrap().0.to_vec(), [2,0,0,0,99]);
+    }
+
+    #[test]
+    fn second_prog() {
+        let prog = vec![2,3,0,3,99];
+        let (intcode_pc, _, _) = IntcodePC::new(prog);
+        assert_eq!(intcode_pc.run().unwrap().0.to_vec(), [2,3,0,6,99]);
+    }
+
+    #[test]
+    fn third_prog() {
+        let prog = vec![2,4,4,5,99,0];
+        let (intcode_pc, _, _) = IntcodePC::new(prog);
+        assert_eq!(intcode_pc.run().unwrap().0.to_vec(), [2,4,4,5,99,9801]);
+    }
+
+    #[test]
+    fn fourth_prog() {
+        let prog = vec![1,1,1,4,99,5,6,0,99];
+        let (intcode_pc, _, _) = IntcodePC::new(prog);
+        assert_eq!(intcode_pc.run().unwrap().0.to_vec(), [30,1,1,4,2,5,6,0,99]);
+    }
+
+    #[test]
+    fn position_mode_78_1() {
+        let prog = vec![3,9,8,9,10,9,4,9,99,-1,8];
+        assert_eq!(run_with_input(prog.clone(), 8), 1);
+        assert_eq!(run_with_input(prog, 6), 0);
+    }
+
+    #[test]
+    fn position_mode_78_2() {
+        let prog = vec![3,9,7,9,10,9,4,9,99,-1,8];
+        assert_eq!(run_with_input(prog.clone(), 6), 1);
+        assert_eq!(run_with_input(prog, 9), 0);
+    }
+
+    #[test]
+    fn immediate_mode_78_1() {
+        let prog = vec![3,3,1108,-1,8,3,4,3,99];
+        assert_eq!(run_with_input(prog.clone(), 8), 1);
+        assert_eq!(run_with_input(prog, 6), 0);
+    }
+
+    #[test]
+    fn immediate_mode_78_2() {
+        let prog = vec![3,3,1107,-1,8,3,4,3,99];
+        assert_eq!(run_with_input(prog.clone(), 5), 1);
+        assert_eq!(run_with_input(prog, 9), 0);
+    }
+
+    #[test]
+    fn jump_position_mode() {
+        let prog = vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9];
+        assert_eq!(run_with_input(prog.clone(), 0), 0);
+        assert_eq!(run_with_input(prog, 1), 1);
+    }
+
+    #[test]
+    fn jump_immediate_mode() {
+        let prog = vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1];
+        assert_eq!(run_with_input(prog.clone(), 0), 0);
+        assert_eq!(run_with_input(prog, 1), 1);
+    }
+
+    #[test]
+    fn relative_base_offset1() {
+        let prog = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        let (intcode_pc, _, _) = IntcodePC::new(prog.clone());
+        let handle = thread::spawn(move || intcode_pc.run());
+        assert_eq!(prog, handle.join().unwrap().unwrap().1);
+    }
+
+    #[test]
+    fn relative_call_and_return() {
+        let prog = assemble("
+                    rbo  #stack
+                    add  #7, #0, rb+1
+                    add  #back, #0, rb+0
+                    jit  #1, #double
+            back:   out  rb+1
+                    hlt
+            double: mul  rb+1, #2, rb+1
+                    jif  #0, rb+0
+            stack:  data 0, 0
+        ").unwrap();
+        assert_eq!(IntcodePC::synchronous(prog).run().unwrap().1, [14]);
+    }
+
+    #[test]
+    fn compare_loop() {
+        let prog = assemble("
+            loop:   in   [value]
+                    lt   [value], #0, [done]
+                    jit  [done], #end
+                    eq   [value], #8, [value]
+                    out  [value]
+                    jit  #1, #loop
+            end:    hlt
+            value:  data 0
+            done:   data 0
+        ").unwrap();
+        let mut intcode_pc = IntcodePC::synchronous(prog);
+        [8, 3, -1].iter().for_each(|&v| intcode_pc.push_input(v));
+        assert_eq!(intcode_pc.run().unwrap().1, [1, 0]);
+    }
+
+    #[test]
+    fn big_number1() {
+        let prog = vec![1102,34915192,34915192,7,4,7,99,0];
+        let (intcode_pc, _, output) = IntcodePC::new(prog);
+        thread::spawn(move || intcode_pc.run());
+        assert_eq!(16, recv_value(&output).to_string().len());
+    }
+
+    #[test]
+    fn big_number2() {
+        let prog = vec![104,1125899906842624,99];
+        let (intcode_pc, _, output) = IntcodePC::new(prog);
+        thread::spawn(move || intcode_pc.run());
+        assert_eq!(recv_value(&output), 1125899906842624);
+    }
+}
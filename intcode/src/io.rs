@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Device attached to a machine with `IntcodePC::run_io` or
+/// `IntcodePC::spawn`.
+pub trait IntcodeIo {
+    /// Next value for an `in` instruction, `None` pauses the machine.
+    fn input(&mut self) -> Option<i64>;
+    fn output(&mut self, value: i64);
+}
+
+impl<T: IntcodeIo + ?Sized> IntcodeIo for &mut T {
+    fn input(&mut self) -> Option<i64> {
+        (**self).input()
+    }
+
+    fn output(&mut self, value: i64) {
+        (**self).output(value)
+    }
+}
+
+/// Fixed list of inputs, outputs are collected.
+#[derive(Debug, Default, Clone)]
+pub struct VecIo {
+    pub inputs: VecDeque<i64>,
+    pub outputs: Vec<i64>,
+}
+
+impl VecIo {
+    pub fn new(inputs: Vec<i64>) -> Self {
+        Self { inputs: inputs.into(), outputs: vec![] }
+    }
+}
+
+impl IntcodeIo for VecIo {
+    fn input(&mut self) -> Option<i64> {
+        self.inputs.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        self.outputs.push(value);
+    }
+}
+
+/// Inputs pulled lazily from an iterator, outputs are collected.
+pub struct IterIo<I> {
+    inputs: I,
+    pub outputs: Vec<i64>,
+}
+
+impl<I: Iterator<Item = i64>> IterIo<I> {
+    pub fn new(inputs: impl IntoIterator<IntoIter = I>) -> Self {
+        Self { inputs: inputs.into_iter(), outputs: vec![] }
+    }
+}
+
+impl<I: Iterator<Item = i64>> IntcodeIo for IterIo<I> {
+    fn input(&mut self) -> Option<i64> {
+        self.inputs.next()
+    }
+
+    fn output(&mut self, value: i64) {
+        self.outputs.push(value);
+    }
+}
+
+/// Plain values over channels, input blocks until a value arrives or every
+/// sender is gone.
+pub struct ChannelIo {
+    input: Receiver<i64>,
+    output: Sender<i64>,
+}
+
+impl ChannelIo {
+    pub fn new() -> (Self, Sender<i64>, Receiver<i64>) {
+        let (in_sender, in_receiver) = channel();
+        let (out_sender, out_receiver) = channel();
+        (Self::connect(in_receiver, out_sender), in_sender, out_receiver)
+    }
+
+    pub fn connect(input: Receiver<i64>, output: Sender<i64>) -> Self {
+        Self { input, output }
+    }
+}
+
+impl IntcodeIo for ChannelIo {
+    fn input(&mut self) -> Option<i64> {
+        self.input.recv().ok()
+    }
+
+    fn output(&mut self, value: i64) {
+        let _ = self.output.send(value);
+    }
+}
+
+/// Wraps a pair of closures.
+pub struct CallbackIo<I, O> {
+    input: I,
+    output: O,
+}
+
+impl<I: FnMut() -> Option<i64>, O: FnMut(i64)> CallbackIo<I, O> {
+    pub fn new(input: I, output: O) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<I: FnMut() -> Option<i64>, O: FnMut(i64)> IntcodeIo for CallbackIo<I, O> {
+    fn input(&mut self) -> Option<i64> {
+        (self.input)()
+    }
+
+    fn output(&mut self, value: i64) {
+        (self.output)(value)
+    }
+}
+
+/// Text in, text out. Outputs outside of ASCII are kept apart in `values`.
+#[derive(Debug, Default, Clone)]
+pub struct AsciiIo {
+    input: VecDeque<i64>,
+    pub text: String,
+    pub values: Vec<i64>,
+}
+
+impl AsciiIo {
+    pub fn new(input: &str) -> Self {
+        let mut io = Self::default();
+        io.push_str(input);
+        io
+    }
+
+    pub fn push_str(&mut self, input: &str) {
+        self.input.extend(input.bytes().map(i64::from));
+    }
+}
+
+impl IntcodeIo for AsciiIo {
+    fn input(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        match value {
+            0..=127 => self.text.push(value as u8 as char),
+            _ => self.values.push(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::intcode_pc::{IntcodePC, Status};
+
+    fn echo() -> Vec<i64> {
+        assemble("
+            loop:   in   [value]
+                    out  [value]
+                    jit  #1, #loop
+            value:  data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn vec_and_iter() {
+        let mut io = VecIo::new(vec![1, 2, 3]);
+        let mut pc = IntcodePC::synchronous(echo());
+        assert_eq!(pc.run_io(&mut io), Ok(Status::NeedsInput));
+        assert_eq!(io.outputs, [1, 2, 3]);
+
+        let mut io = IterIo::new((1..).take(5).map(|v| v * v));
+        assert_eq!(IntcodePC::synchronous(echo()).run_io(&mut io), Ok(Status::NeedsInput));
+        assert_eq!(io.outputs, [1, 4, 9, 16, 25]);
+    }
+
+    #[test]
+    fn callback_and_ascii() {
+        let mut sum = 0;
+        let mut counter = 0;
+        let io = CallbackIo::new(|| { counter += 1; Some(counter).filter(|&c| c <= 4) }, |v| sum += v);
+        let mut pc = IntcodePC::synchronous(echo());
+        assert_eq!(pc.run_io(io), Ok(Status::NeedsInput));
+        assert_eq!(sum, 10);
+
+        let mut io = AsciiIo::new("Hi!\n");
+        io.input.push_back(1000);
+        IntcodePC::synchronous(echo()).run_io(&mut io).unwrap();
+        assert_eq!(io.text, "Hi!\n");
+        assert_eq!(io.values, [1000]);
+    }
+
+    #[test]
+    fn threaded_channel() {
+        let (io, input, output) = ChannelIo::new();
+        let handle = IntcodePC::synchronous(echo()).spawn(io);
+        for v in 1..=3 {
+            input.send(v).unwrap();
+            assert_eq!(output.recv(), Ok(v));
+        }
+        drop(input);
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn halting_device() {
+        let prog = assemble("
+                    in   [value]
+                    mul  [value], #3, [value]
+                    out  [value]
+                    hlt
+            value:  data 0
+        ").unwrap();
+        let handle = IntcodePC::synchronous(prog).spawn(VecIo::new(vec![14]));
+        assert_eq!(handle.join().unwrap().unwrap().outputs, [42]);
+    }
+}
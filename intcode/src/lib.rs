@@ -0,0 +1,24 @@
+pub mod ascii;
+pub mod asm;
+pub mod async_pc;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod fast;
+pub mod fuzz;
+pub mod intcode_pc;
+pub mod io;
+pub mod limits;
+pub mod memory;
+pub mod network;
+pub mod profile;
+pub mod smc;
+pub mod snapshot;
+pub mod solver;
+pub mod symbolic;
+pub mod trace;
+
+pub use error::IntcodeError;
+pub use intcode_pc::{load_prog, parse_prog, read_prog, IntcodePC, Message, Status};
+pub use io::IntcodeIo;
+pub use snapshot::Snapshot;
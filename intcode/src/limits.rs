@@ -0,0 +1,183 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::IntcodeError;
+use crate::intcode_pc::{IntcodePC, Status};
+
+/// How often the clock and the cancellation flag are looked at.
+const CHECK_EVERY: u64 = 1024;
+
+/// Bounds for `IntcodePC::run_limited`, `None` means unlimited.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Limits {
+    pub instructions: Option<u64>,
+    pub time: Option<Duration>,
+    pub outputs: Option<usize>,
+    /// Highest writable address plus one, see `IntcodePC::set_memory_limit`.
+    pub memory: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StopReason {
+    Halted,
+    NeedsInput,
+    InstructionLimit,
+    TimeLimit,
+    OutputLimit,
+    MemoryLimit { addr: usize },
+    Cancelled,
+    Error(IntcodeError),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RunReport {
+    pub reason: StopReason,
+    pub outputs: Vec<i64>,
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+/// Stops a `run_limited` call from any thread.
+#[derive(Debug, Default, Clone)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Clears a cancellation so the machine can be run again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+impl IntcodePC {
+    /// Runs until the machine halts, waits for input or hits one of
+    /// `limits`. The machine can be resumed afterwards, with the memory
+    /// limit it had before the call.
+    pub fn run_limited(&mut self, limits: Limits) -> RunReport {
+        let previous = limits.memory.map(|memory| self.replace_memory_limit(Some(memory)));
+        let cancel = self.cancel_handle();
+        let start = Instant::now();
+        let mut report = RunReport {
+            reason: StopReason::Halted,
+            outputs: vec![],
+            instructions: 0,
+            elapsed: Duration::default(),
+        };
+        report.reason = loop {
+            if report.instructions % CHECK_EVERY == 0 {
+                if cancel.is_cancelled() {
+                    break StopReason::Cancelled;
+                }
+                if limits.time.is_some_and(|time| start.elapsed() >= time) {
+                    break StopReason::TimeLimit;
+                }
+            }
+            if limits.instructions.is_some_and(|max| report.instructions >= max) {
+                break StopReason::InstructionLimit;
+            }
+            match self.step() {
+                Ok(Status::Halted) => break StopReason::Halted,
+                Ok(Status::NeedsInput) => break StopReason::NeedsInput,
+                Ok(Status::Output(v)) => report.outputs.push(v),
+                Ok(Status::Running) => (),
+                Err(IntcodeError::MemoryLimit { addr, .. }) => break StopReason::MemoryLimit { addr },
+                Err(e) => break StopReason::Error(e),
+            }
+            report.instructions += 1;
+            if limits.outputs.is_some_and(|max| report.outputs.len() >= max) {
+                break StopReason::OutputLimit;
+            }
+        };
+        report.elapsed = start.elapsed();
+        if let Some(limit) = previous {
+            self.replace_memory_limit(limit);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::asm::assemble;
+
+    fn counter() -> Vec<i64> {
+        assemble("
+            loop:   add  [count], #1, [count]
+                    out  [count]
+                    jit  #1, #loop
+            count:  data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn instructions_and_outputs() {
+        let mut pc = IntcodePC::synchronous(counter());
+        let report = pc.run_limited(Limits { instructions: Some(7), ..Limits::default() });
+        assert_eq!(report.reason, StopReason::InstructionLimit);
+        assert_eq!((report.instructions, report.outputs), (7, vec![1, 2]));
+
+        let report = pc.run_limited(Limits { outputs: Some(2), ..Limits::default() });
+        assert_eq!(report.reason, StopReason::OutputLimit);
+        assert_eq!(report.outputs, [3, 4]);
+    }
+
+    #[test]
+    fn time() {
+        let mut pc = IntcodePC::synchronous(counter());
+        let limit = Duration::from_millis(20);
+        let report = pc.run_limited(Limits { time: Some(limit), ..Limits::default() });
+        assert_eq!(report.reason, StopReason::TimeLimit);
+        assert!(report.elapsed >= limit);
+    }
+
+    #[test]
+    fn memory_and_errors() {
+        let mut pc = IntcodePC::synchronous(vec![1101, 1, 2, 1000, 1101, 1, 2, 2000, 99]);
+        let report = pc.run_limited(Limits { memory: Some(1024), ..Limits::default() });
+        assert_eq!((report.reason, report.instructions), (StopReason::MemoryLimit { addr: 2000 }, 1));
+        // The limit was only for that call.
+        assert_eq!(pc.run_limited(Limits::default()).reason, StopReason::Halted);
+        assert_eq!(pc.peek(2000), 3);
+
+        let mut pc = IntcodePC::synchronous(vec![1101, 1, 2, 1000, 1101, 1, 2, 2000, 99]);
+        pc.set_memory_limit(1500);
+        assert_eq!(pc.run_limited(Limits { memory: Some(4000), ..Limits::default() }).reason, StopReason::Halted);
+        let mut pc = IntcodePC::synchronous(vec![1101, 1, 2, 2000, 99]);
+        pc.set_memory_limit(1500);
+        pc.run_limited(Limits { memory: Some(4000), instructions: Some(0), ..Limits::default() });
+        assert_eq!(pc.run_limited(Limits::default()).reason, StopReason::MemoryLimit { addr: 2000 });
+
+        let report = IntcodePC::synchronous(vec![42]).run_limited(Limits::default());
+        assert_eq!(report.reason, StopReason::Error(IntcodeError::UnknownOpcode { pc: 0, raw: 42 }));
+        let report = IntcodePC::synchronous(vec![3, 0, 99]).run_limited(Limits::default());
+        assert_eq!((report.reason, report.instructions), (StopReason::NeedsInput, 0));
+    }
+
+    #[test]
+    fn cancel_from_another_thread() {
+        let mut pc = IntcodePC::synchronous(counter());
+        let cancel = pc.cancel_handle();
+        let handle = thread::spawn(move || {
+            let report = pc.run_limited(Limits::default());
+            (pc, report)
+        });
+        thread::sleep(Duration::from_millis(10));
+        cancel.cancel();
+        let (mut pc, report) = handle.join().unwrap();
+        assert_eq!(report.reason, StopReason::Cancelled);
+
+        cancel.reset();
+        let report = pc.run_limited(Limits { outputs: Some(1), ..Limits::default() });
+        assert_eq!(report.reason, StopReason::OutputLimit);
+    }
+}
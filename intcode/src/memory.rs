@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{TryFrom, TryInto};
+use std::ops::Index;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::IntcodeError;
+
+/// Memory contents without the gaps: the cells from address 0 to the end
+/// of the program plus the cells written past it. Cheap to take however
+/// high the program wrote. Images are equal when they have the same length
+/// and the same values at every address.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawImage")]
+pub struct Image {
+    program: Vec<i64>,
+    written: BTreeMap<usize, i64>,
+    len: usize,
+}
+
+#[derive(Deserialize)]
+struct RawImage {
+    program: Vec<i64>,
+    written: BTreeMap<usize, i64>,
+    len: usize,
+}
+
+impl TryFrom<RawImage> for Image {
+    type Error = String;
+
+    fn try_from(raw: RawImage) -> Result<Self, String> {
+        let RawImage { program, written, len } = raw;
+        if len < program.len() {
+            return Err(format!("length {} is shorter than the {} program cells", len, program.len()));
+        }
+        if let Some(&addr) = written.keys().find(|&&addr| addr < program.len() || addr >= len) {
+            return Err(format!("written address {} is outside {}..{}", addr, program.len(), len));
+        }
+        Ok(Self { program, written, len })
+    }
+}
+
+impl PartialEq for Image {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.nonzero().eq(other.nonzero())
+    }
+}
+
+impl Eq for Image {}
+
+impl Image {
+    pub fn new(program: Vec<i64>) -> Self {
+        Self { len: program.len(), program, written: BTreeMap::new() }
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        self[addr]
+    }
+
+    pub fn set(&mut self, addr: usize, value: i64) {
+        match self.program.get_mut(addr) {
+            Some(cell) => *cell = value,
+            None => {
+                self.written.insert(addr, value);
+                self.len = self.len.max(addr + 1);
+            },
+        }
+    }
+
+    /// One past the highest address holding the program or a written value.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Cells from address 0 to the end of the program.
+    pub fn program(&self) -> &[i64] {
+        &self.program
+    }
+
+    /// Cells written past the program, by address.
+    pub fn written(&self) -> impl Iterator<Item = (usize, i64)> + '_ {
+        self.written.iter().map(|(&addr, &value)| (addr, value))
+    }
+
+    /// The program cells followed by the ones written past it.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, i64)> + '_ {
+        self.program.iter().copied().enumerate().chain(self.written())
+    }
+
+    /// Every cell up to `len`, as large as the highest written address.
+    pub fn to_vec(&self) -> Vec<i64> {
+        (0..self.len).map(|addr| self[addr]).collect()
+    }
+
+    fn nonzero(&self) -> impl Iterator<Item = (usize, i64)> + '_ {
+        self.cells().filter(|&(_, value)| value != 0)
+    }
+}
+
+impl Index<usize> for Image {
+    type Output = i64;
+
+    fn index(&self, addr: usize) -> &i64 {
+        self.program.get(addr).or_else(|| self.written.get(&addr)).unwrap_or(&0)
+    }
+}
+
+/// Storage backing an `IntcodePC`. Cells that were never written read as
+/// zero.
+pub trait Memory: Send {
+    fn get(&self, addr: usize) -> i64;
+    fn set(&mut self, addr: usize, value: i64);
+    /// One past the highest address holding the program or a written value.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn image(&self) -> Image;
+}
+
+/// Single vector growing up to the highest written address.
+pub struct Dense(Vec<i64>);
+
+impl Dense {
+    pub fn new(program: Vec<i64>) -> Self {
+        Self(program)
+    }
+}
+
+impl Memory for Dense {
+    fn get(&self, addr: usize) -> i64 {
+        self.0.get(addr).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, addr: usize, value: i64) {
+        if addr >= self.0.len() {
+            self.0.resize(addr + 1, 0);
+        }
+        self.0[addr] = value;
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn image(&self) -> Image {
+        Image::new(self.0.clone())
+    }
+}
+
+pub const PAGE_SIZE: usize = 1024;
+
+/// The program image stays contiguous, everything written past it lands
+/// in pages allocated on first write.
+pub struct Paged {
+    image: Vec<i64>,
+    pages: HashMap<usize, Box<[i64; PAGE_SIZE]>>,
+    len: usize,
+}
+
+impl Paged {
+    pub fn new(program: Vec<i64>) -> Self {
+        Self { len: program.len(), image: program, pages: HashMap::new() }
+    }
+
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl Memory for Paged {
+    fn get(&self, addr: usize) -> i64 {
+        match self.image.get(addr) {
+            Some(&value) => value,
+            None => self.pages.get(&(addr / PAGE_SIZE)).map_or(0, |page| page[addr % PAGE_SIZE]),
+        }
+    }
+
+    fn set(&mut self, addr: usize, value: i64) {
+        match self.image.get_mut(addr) {
+            Some(cell) => *cell = value,
+            None => {
+                let page = self.pages.entry(addr / PAGE_SIZE).or_insert_with(|| Box::new([0; PAGE_SIZE]));
+                page[addr % PAGE_SIZE] = value;
+                self.len = self.len.max(addr + 1);
+            },
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn image(&self) -> Image {
+        let mut image = Image::new(self.image.clone());
+        let mut pages: Vec<_> = self.pages.iter().collect();
+        pages.sort_unstable_by_key(|&(&n, _)| n);
+        for (&n, page) in pages {
+            for (offset, &value) in page.iter().enumerate().filter(|&(_, &value)| value != 0) {
+                image.set(n * PAGE_SIZE + offset, value);
+            }
+        }
+        image.len = self.len;
+        image
+    }
+}
+
+impl From<Image> for Paged {
+    fn from(image: Image) -> Self {
+        let mut paged = Paged::new(image.program);
+        for (addr, value) in image.written {
+            paged.set(addr, value);
+        }
+        paged.len = paged.len.max(image.len);
+        paged
+    }
+}
+
+/// Address problems found before touching the backend, turned into an
+/// `IntcodeError` once the failing instruction is known.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Fault {
+    Negative(i64),
+    Limit { addr: usize, limit: usize },
+}
+
+impl Fault {
+    pub(crate) fn at(self, pc: usize, raw: i64) -> IntcodeError {
+        match self {
+            Fault::Negative(addr) => IntcodeError::NegativeAddress { pc, raw, addr },
+            Fault::Limit { addr, limit } => IntcodeError::MemoryLimit { pc, raw, addr, limit },
+        }
+    }
+}
+
+/// Backend plus the optional limit on writable addresses. Reads past the
+/// limit are allowed, they never allocate.
+pub(crate) struct Ram {
+    pub(crate) cells: Box<dyn Memory>,
+    pub(crate) limit: Option<usize>,
+}
+
+impl Ram {
+    pub(crate) fn new(cells: Box<dyn Memory>) -> Self {
+        Self { cells, limit: None }
+    }
+
+    pub(crate) fn read(&self, addr: i64) -> Result<i64, Fault> {
+        let addr: usize = addr.try_into().map_err(|_| Fault::Negative(addr))?;
+        Ok(self.cells.get(addr))
+    }
+
+    pub(crate) fn write(&mut self, addr: i64, value: i64) -> Result<(), Fault> {
+        let addr: usize = addr.try_into().map_err(|_| Fault::Negative(addr))?;
+        match self.limit {
+            Some(limit) if addr >= limit => Err(Fault::Limit { addr, limit }),
+            _ => {
+                self.cells.set(addr, value);
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dense_boundaries() {
+        let mut memory = Dense::new(vec![1, 2, 3]);
+        assert_eq!(memory.get(3), 0);
+        memory.set(3, 4);
+        assert_eq!(memory.image().to_vec(), [1, 2, 3, 4]);
+        memory.set(6, 7);
+        assert_eq!(memory.len(), 7);
+    }
+
+    #[test]
+    fn paged_is_sparse() {
+        let mut memory = Paged::new(vec![1, 2, 3]);
+        memory.set(3, 4);
+        memory.set(1_000_000_000, 5);
+        assert_eq!(memory.pages(), 2);
+        assert_eq!(memory.get(2), 3);
+        assert_eq!(memory.get(3), 4);
+        assert_eq!(memory.get(1_000_000_000), 5);
+        assert_eq!(memory.get(999_999_999), 0);
+        assert_eq!(memory.len(), 1_000_000_001);
+        memory.set(1, 0);
+        assert_eq!(memory.get(1), 0);
+        assert_eq!(memory.pages(), 2);
+
+        let image = memory.image();
+        assert_eq!(image.len(), 1_000_000_001);
+        assert_eq!(image.cells().collect::<Vec<_>>(), [(0, 1), (1, 0), (2, 3), (3, 4), (1_000_000_000, 5)]);
+        assert_eq!((image[1_000_000_000], image[7]), (5, 0));
+        let restored = Paged::from(image.clone());
+        assert_eq!((restored.len(), restored.pages()), (1_000_000_001, 2));
+        assert_eq!(restored.image(), image);
+    }
+
+    #[test]
+    fn image_equality() {
+        let mut sparse = Image::new(vec![1, 0]);
+        sparse.set(5, 0);
+        let mut dense = Image::new(vec![1, 0, 0, 0, 0, 0]);
+        assert_eq!(sparse, dense);
+        dense.set(6, 0);
+        assert_ne!(sparse, dense);
+        sparse.set(6, 2);
+        assert_ne!(sparse, dense);
+    }
+
+    #[test]
+    fn image_validation() {
+        let image: Image = serde_json::from_str(r#"{"program":[1,2],"written":{"5":3},"len":6}"#).unwrap();
+        assert_eq!(image.to_vec(), [1, 2, 0, 0, 0, 3]);
+        for invalid in &[r#"{"program":[1,2],"written":{},"len":1}"#,
+                         r#"{"program":[1,2],"written":{"5":3},"len":5}"#,
+                         r#"{"program":[1,2],"written":{"1":3},"len":2}"#] {
+            assert!(serde_json::from_str::<Image>(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn ram_limit() {
+        let mut ram = Ram::new(Box::new(Paged::new(vec![0; 4])));
+        ram.limit = Some(8);
+        assert_eq!(ram.write(7, 1), Ok(()));
+        assert_eq!(ram.write(8, 1), Err(Fault::Limit { addr: 8, limit: 8 }));
+        assert_eq!(ram.write(-1, 1), Err(Fault::Negative(-1)));
+        assert_eq!(ram.read(100), Ok(0));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::error::IntcodeError;
+use crate::intcode_pc::{IntcodePC, Status};
+
+/// Why `Network::run` returned.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NetworkStatus {
+    Halted,
+    /// Every running machine waits for input nobody is going to send.
+    Idle,
+}
+
+/// Packet addressed outside of the network.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+enum Routing {
+    /// Output of machine `n` goes to the input of `links[n]`.
+    Links(Vec<Option<usize>>),
+    /// Outputs are `dest, x, y` triples, a machine with nothing queued reads
+    /// `idle_value`.
+    Packets { idle_value: i64, partial: Vec<Vec<i64>> },
+}
+
+/// Machines run cooperatively on the current thread, each one until it
+/// blocks on input or halts.
+pub struct Network {
+    machines: Vec<IntcodePC>,
+    halted: Vec<bool>,
+    last_output: Vec<Option<i64>>,
+    routing: Routing,
+    outputs: Vec<(usize, i64)>,
+    packets: Vec<Packet>,
+}
+
+impl Network {
+    /// Unconnected machines, every output ends up in `outputs`.
+    pub fn new(programs: Vec<Vec<i64>>) -> Self {
+        let count = programs.len();
+        Self {
+            machines: programs.into_iter().map(IntcodePC::synchronous).collect(),
+            halted: vec![false; count],
+            last_output: vec![None; count],
+            routing: Routing::Links(vec![None; count]),
+            outputs: vec![],
+            packets: vec![],
+        }
+    }
+
+    /// `count` copies of `program`, each one feeding the next.
+    pub fn chain(program: &[i64], count: usize) -> Self {
+        let mut network = Self::new(vec![program.to_vec(); count]);
+        for n in 1..count {
+            network.connect(n - 1, n);
+        }
+        network
+    }
+
+    /// Chain with the last machine feeding the first.
+    pub fn ring(program: &[i64], count: usize) -> Self {
+        let mut network = Self::chain(program, count);
+        if count > 0 {
+            network.connect(count - 1, 0);
+        }
+        network
+    }
+
+    /// `count` copies of `program` exchanging packets. Machine `n` gets its
+    /// address as the first input.
+    pub fn packets(program: &[i64], count: usize, idle_value: i64) -> Self {
+        let mut network = Self::new(vec![program.to_vec(); count]);
+        network.routing = Routing::Packets { idle_value, partial: vec![vec![]; count] };
+        for n in 0..count {
+            network.push_input(n, n as i64);
+        }
+        network
+    }
+
+    /// Routes outputs of `from` to `to`. Panics in packet mode.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.machines.len(), "No machine {} to connect to", to);
+        match &mut self.routing {
+            Routing::Links(links) => links[from] = Some(to),
+            Routing::Packets {..} => panic!("Packet networks route by address"),
+        }
+    }
+
+    pub fn push_input(&mut self, machine: usize, value: i64) {
+        self.machines[machine].push_input(value);
+    }
+
+    pub fn machine(&self, machine: usize) -> &IntcodePC {
+        &self.machines[machine]
+    }
+
+    pub fn last_output(&self, machine: usize) -> Option<i64> {
+        self.last_output[machine]
+    }
+
+    /// Outputs of unconnected machines, tagged with their sender.
+    pub fn outputs(&self) -> &[(usize, i64)] {
+        &self.outputs
+    }
+
+    /// Packets sent to addresses outside of the network.
+    pub fn take_packets(&mut self) -> Vec<Packet> {
+        self.packets.drain(..).collect()
+    }
+
+    fn route(&mut self, from: usize, value: i64) {
+        self.last_output[from] = Some(value);
+        match &mut self.routing {
+            Routing::Links(links) => match links[from] {
+                Some(to) => self.machines[to].push_input(value),
+                None => self.outputs.push((from, value)),
+            },
+            Routing::Packets { partial, .. } => {
+                partial[from].push(value);
+                if let [dest, x, y] = partial[from][..] {
+                    partial[from].clear();
+                    if (0..self.machines.len() as i64).contains(&dest) {
+                        self.machines[dest as usize].push_input(x);
+                        self.machines[dest as usize].push_input(y);
+                    } else {
+                        self.packets.push(Packet { dest, x, y });
+                    }
+                }
+            },
+        }
+    }
+
+    /// Gives every machine a turn until all of them halt or the whole
+    /// network goes a round without doing anything.
+    pub fn run(&mut self) -> Result<NetworkStatus, IntcodeError> {
+        loop {
+            if self.halted.iter().all(|&h| h) {
+                return Ok(NetworkStatus::Halted);
+            }
+            let mut busy = false;
+            for n in 0..self.machines.len() {
+                if self.halted[n] {
+                    continue;
+                }
+                let (start, queued) = (self.machines[n].pc(), !self.machines[n].pending_input().is_empty());
+                busy |= queued;
+                if let (Routing::Packets { idle_value, .. }, false) = (&self.routing, queued) {
+                    self.machines[n].push_input(*idle_value);
+                }
+                loop {
+                    match self.machines[n].run_until_io()? {
+                        Status::Output(v) => {
+                            self.route(n, v);
+                            busy = true;
+                        },
+                        Status::Halted => {
+                            self.halted[n] = true;
+                            busy = true;
+                            break;
+                        },
+                        _ => {
+                            busy |= self.machines[n].pc() != start && matches!(self.routing, Routing::Links(_));
+                            break;
+                        },
+                    }
+                }
+            }
+            if !busy {
+                return Ok(NetworkStatus::Idle);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn add_phase() -> Vec<i64> {
+        assemble("
+                    in   [phase]
+            loop:   in   [value]
+                    add  [value], [phase], [value]
+                    out  [value]
+                    jit  #1, #loop
+            phase:  data 0
+            value:  data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn chain() {
+        let mut network = Network::chain(&add_phase(), 3);
+        (0..3).for_each(|n| network.push_input(n, 10_i64.pow(n as u32)));
+        network.push_input(0, 0);
+        network.push_input(0, 1000);
+        assert_eq!(network.run(), Ok(NetworkStatus::Idle));
+        assert_eq!(network.outputs(), [(2, 111), (2, 1111)]);
+        assert_eq!(network.last_output(1), Some(1011));
+    }
+
+    #[test]
+    fn ring_until_halt() {
+        let prog = assemble("
+                    in   [value]
+                    mul  [value], #2, [value]
+                    out  [value]
+                    hlt
+            value:  data 0
+        ").unwrap();
+        let mut network = Network::ring(&prog, 4);
+        network.push_input(0, 1);
+        assert_eq!(network.run(), Ok(NetworkStatus::Halted));
+        assert_eq!(network.last_output(3), Some(16));
+        assert_eq!(network.machine(0).pending_input(), &[16]);
+        assert!(network.outputs().is_empty());
+    }
+
+    #[test]
+    fn deadlock_is_idle() {
+        let mut network = Network::ring(&add_phase(), 2);
+        network.push_input(0, 1);
+        network.push_input(1, 2);
+        assert_eq!(network.run(), Ok(NetworkStatus::Idle));
+        assert_eq!(network.machine(0).pc(), 2);
+        assert!(network.last_output(0).is_none());
+    }
+
+    #[test]
+    fn packets() {
+        let prog = assemble("
+                    in   [addr]
+                    add  [addr], #1, [dest]
+            loop:   in   [x]
+                    eq   [x], #-1, [tmp]
+                    jit  [tmp], #loop
+                    in   [y]
+                    out  [dest]
+                    out  [x]
+                    add  [y], #1, [y]
+                    out  [y]
+                    jit  #1, #loop
+            addr:   data 0
+            dest:   data 0
+            x:      data 0
+            y:      data 0
+            tmp:    data 0
+        ").unwrap();
+        let mut network = Network::packets(&prog, 3, -1);
+        assert_eq!(network.run(), Ok(NetworkStatus::Idle));
+        network.push_input(0, 5);
+        network.push_input(0, 0);
+        assert_eq!(network.run(), Ok(NetworkStatus::Idle));
+        assert_eq!(network.take_packets(), [Packet { dest: 3, x: 5, y: 3 }]);
+        assert!(network.take_packets().is_empty());
+    }
+}
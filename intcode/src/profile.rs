@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use crate::disasm::{self, Line};
+use crate::trace::TraceEvent;
+
+/// Execution counts and memory coverage collected by
+/// `IntcodePC::enable_profiling`.
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    executed: HashMap<usize, u64>,
+    opcodes: BTreeMap<String, u64>,
+    code_words: BTreeSet<usize>,
+    reads: BTreeSet<usize>,
+    writes: BTreeSet<usize>,
+    loops: HashMap<(usize, usize), u64>,
+}
+
+impl Profile {
+    /// `reads` are the addresses the instruction took its arguments from,
+    /// `next_pc` is where the machine continued.
+    pub(crate) fn record(&mut self, event: &TraceEvent, reads: &[usize], next_pc: usize) {
+        *self.executed.entry(event.pc).or_insert(0) += 1;
+        *self.opcodes.entry(event.op.clone()).or_insert(0) += 1;
+        self.code_words.extend(event.pc..=event.pc + event.args.len());
+        self.reads.extend(reads);
+        if let Some((addr, _)) = event.write {
+            self.writes.insert(addr);
+        }
+        if next_pc <= event.pc && matches!(event.op.as_str(), "jit" | "jif") {
+            *self.loops.entry((next_pc, event.pc)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.executed.values().sum()
+    }
+
+    pub fn executions(&self, addr: usize) -> u64 {
+        self.executed.get(&addr).cloned().unwrap_or(0)
+    }
+
+    pub fn opcodes(&self) -> &BTreeMap<String, u64> {
+        &self.opcodes
+    }
+
+    /// Whether `addr` was ever executed, as an opcode or as a parameter.
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.code_words.contains(&addr)
+    }
+
+    /// Cells read or written, but never executed.
+    pub fn data(&self) -> BTreeSet<usize> {
+        self.reads.union(&self.writes)
+            .filter(|addr| !self.code_words.contains(addr))
+            .cloned()
+            .collect()
+    }
+
+    /// Executed cells that were also written to.
+    pub fn self_modified(&self) -> BTreeSet<usize> {
+        self.writes.intersection(&self.code_words).cloned().collect()
+    }
+
+    /// Backward jumps as `(start, end, iterations)`, most taken first.
+    pub fn hot_loops(&self) -> Vec<(usize, usize, u64)> {
+        self.loops.iter()
+            .map(|(&(start, end), &count)| (start, end, count))
+            .sorted_by_key(|&(start, end, count)| (std::cmp::Reverse(count), start, end))
+            .collect()
+    }
+
+    /// Instructions of `program`'s disassembly that never ran.
+    pub fn unexecuted(&self, program: &[i64]) -> Vec<Line> {
+        disasm::disassemble(program).into_iter()
+            .filter(|line| matches!(line, Line::Code {..}) && !self.executed.contains_key(&line.addr()))
+            .collect()
+    }
+
+    pub fn report(&self, program: &[i64]) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "{} instructions executed", self.instructions());
+        for (op, count) in self.opcodes.iter().sorted_by_key(|&(_, &count)| std::cmp::Reverse(count)) {
+            let _ = writeln!(report, "{:>8} {}", count, op);
+        }
+
+        let _ = writeln!(report, "\nhottest instructions:");
+        let hottest = self.executed.iter().sorted_by_key(|&(&addr, &count)| (std::cmp::Reverse(count), addr));
+        for (&addr, count) in hottest.take(10) {
+            let _ = writeln!(report, "{:>8} {}", count, disasm::line_at(program, addr));
+        }
+
+        let _ = writeln!(report, "\nhot loops:");
+        for (start, end, count) in self.hot_loops().into_iter().take(10) {
+            let _ = writeln!(report, "{:>8} {}..={}", count, start, end);
+        }
+
+        let unexecuted = self.unexecuted(program);
+        let _ = writeln!(report, "\n{} instructions never executed:", unexecuted.len());
+        for line in unexecuted {
+            let _ = writeln!(report, "{}", line);
+        }
+
+        let _ = writeln!(report, "\n{} cells used as data only", self.data().len());
+        let modified = self.self_modified();
+        let _ = write!(report, "{} self-modified cells", modified.len());
+        if !modified.is_empty() {
+            let _ = write!(report, ": {}", modified.iter().join(", "));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::intcode_pc::IntcodePC;
+
+    fn countdown() -> Vec<i64> {
+        assemble("
+                    in   [counter]
+            loop:   add  [counter], #-1, [counter]
+                    out  [counter]
+                    jit  [counter], #loop
+                    jif  [counter], #done
+                    out  #1
+            done:   hlt
+            counter: data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn same_results() {
+        let mut pc = IntcodePC::synchronous(countdown());
+        pc.enable_profiling();
+        pc.push_input(3);
+        assert_eq!(pc.run().unwrap().1, [2, 1, 0]);
+    }
+
+    #[test]
+    fn coverage() {
+        let prog = countdown();
+        let mut pc = IntcodePC::synchronous(prog.clone());
+        pc.enable_profiling();
+        pc.push_input(3);
+        while pc.step().unwrap() != crate::Status::Halted {}
+        let profile = pc.profile().unwrap();
+        assert_eq!(profile.instructions(), 1 + 3 * 3 + 2);
+        assert_eq!(profile.executions(2), 3);
+        assert_eq!(profile.opcodes()["add"], 3);
+        assert_eq!(profile.hot_loops(), [(2, 8, 2)]);
+        assert!(profile.is_code(1) && !profile.is_code(15));
+        assert_eq!(profile.data().into_iter().collect::<Vec<_>>(), [17]);
+        assert!(profile.self_modified().is_empty());
+        assert_eq!(profile.unexecuted(&prog).len(), 1);
+        let report = profile.report(&prog);
+        assert!(report.starts_with("12 instructions executed\n"));
+        assert!(report.contains("       2 2..=8\n"));
+        assert!(report.contains("1 instructions never executed:\n   14: out  #1"));
+    }
+
+    #[test]
+    fn self_modification() {
+        let prog = assemble("
+                    add  #1100, #1, [patched]
+            patched: mul  #20, #22, [result]
+                    hlt
+            result: data 0
+        ").unwrap();
+        let mut pc = IntcodePC::synchronous(prog);
+        pc.enable_profiling();
+        pc.run_until_io().unwrap();
+        let profile = pc.profile().unwrap();
+        assert_eq!(profile.self_modified().into_iter().collect::<Vec<_>>(), [4]);
+        assert_eq!(pc.peek(9), 42);
+    }
+}
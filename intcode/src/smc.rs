@@ -0,0 +1,117 @@
+use std::collections::{BTreeSet, HashSet};
+
+/// Write landing on an address that was already executed as part of an
+/// instruction.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SelfModification {
+    pub pc: usize,
+    pub addr: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+pub type SmcHook = Box<dyn FnMut(&SelfModification) + Send>;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct SmcStats {
+    pub writes: u64,
+    pub addresses: BTreeSet<usize>,
+}
+
+/// State behind `IntcodePC::detect_self_modification`.
+pub(crate) struct SmcDetector {
+    executed: HashSet<usize>,
+    pub(crate) strict: bool,
+    pub(crate) hook: Option<SmcHook>,
+    pub(crate) stats: SmcStats,
+}
+
+impl SmcDetector {
+    pub(crate) fn new(strict: bool) -> Self {
+        Self { executed: HashSet::new(), strict, hook: None, stats: SmcStats::default() }
+    }
+
+    pub(crate) fn executing(&mut self, pc: usize, words: usize) {
+        self.executed.extend(pc..pc + words);
+    }
+
+    pub(crate) fn is_executed(&self, addr: usize) -> bool {
+        self.executed.contains(&addr)
+    }
+
+    pub(crate) fn record(&mut self, modification: SelfModification) {
+        self.stats.writes += 1;
+        self.stats.addresses.insert(modification.addr);
+        if let Some(hook) = self.hook.as_mut() {
+            hook(&modification);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use crate::asm::assemble;
+    use crate::error::IntcodeError;
+    use crate::intcode_pc::{load_prog, IntcodePC, Status};
+
+    fn patching() -> Vec<i64> {
+        assemble("
+            start:  jif  [flag], #patch
+                    hlt
+            patch:  add  #1, #0, [flag]
+                    add  #99, #0, [start]
+                    jit  #1, #start
+            flag:   data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn writes_to_executed_code() {
+        let (sender, receiver) = channel();
+        let mut pc = IntcodePC::synchronous(patching());
+        pc.on_self_modification(Box::new(move |m| sender.send(*m).unwrap()));
+        pc.step().unwrap();
+        pc.step().unwrap();
+        assert!(receiver.try_recv().is_err());
+        pc.step().unwrap();
+        assert_eq!(receiver.try_recv(), Ok(SelfModification { pc: 8, addr: 0, old: 1006, new: 99 }));
+        assert_eq!(pc.run_until_io(), Ok(Status::Halted));
+        let stats = pc.self_modification_stats().unwrap();
+        assert_eq!(stats.writes, 1);
+        assert_eq!(stats.addresses.iter().collect::<Vec<_>>(), [&0]);
+    }
+
+    #[test]
+    fn strict_mode() {
+        let mut pc = IntcodePC::synchronous(patching());
+        pc.detect_self_modification(true);
+        assert_eq!(pc.run_until_io(), Err(IntcodeError::SelfModifying { pc: 8, raw: 1101, addr: 0 }));
+        assert_eq!(pc.peek(0), 1006);
+    }
+
+    #[test]
+    fn day_programs() {
+        let mut day2 = load_prog(include_str!("../../day2/input"));
+        day2[1] = 12;
+        day2[2] = 2;
+        // Every instruction stores its result over one of its own words.
+        let mut pc = IntcodePC::synchronous(day2.clone());
+        pc.detect_self_modification(true);
+        assert_eq!(pc.run(), Err(IntcodeError::SelfModifying { pc: 0, raw: 1, addr: 3 }));
+        let mut pc = IntcodePC::synchronous(day2);
+        pc.detect_self_modification(false);
+        pc.run_until_io().unwrap();
+        let stats = pc.self_modification_stats().unwrap();
+        assert_eq!((stats.writes, stats.addresses.len()), (36, 33));
+
+        let day5 = load_prog(include_str!("../../day5/input"));
+        let mut pc = IntcodePC::synchronous(day5);
+        pc.detect_self_modification(false);
+        pc.push_input(5);
+        pc.run_until_io().unwrap();
+        let stats = pc.self_modification_stats().unwrap();
+        assert_eq!((stats.writes, stats.addresses.iter().collect::<Vec<_>>()), (2, vec![&0]));
+    }
+}
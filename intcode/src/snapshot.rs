@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::memory::Image;
+
+/// Complete state of a machine, see `IntcodePC::snapshot` and
+/// `IntcodePC::restore`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub memory: Image,
+    pub pc: usize,
+    pub rel_base: i64,
+    pub inputs: VecDeque<i64>,
+}
+
+impl Snapshot {
+    pub fn save(&self, writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer(writer, self).map_err(io::Error::from)
+    }
+
+    pub fn load(reader: impl Read) -> io::Result<Self> {
+        serde_json::from_reader(reader).map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::error::IntcodeError;
+    use crate::intcode_pc::{IntcodePC, Status};
+
+    fn counter() -> Vec<i64> {
+        assemble("
+            loop:   in   [step]
+                    add  [total], [step], [total]
+                    out  [total]
+                    jit  #1, #loop
+            step:   data 0
+            total:  data 0
+        ").unwrap()
+    }
+
+    #[test]
+    fn fork_is_independent() {
+        let mut pc = IntcodePC::synchronous(counter());
+        pc.push_input(5);
+        assert_eq!(pc.run_until_io().unwrap(), Status::Output(5));
+        let mut fork = pc.fork();
+        pc.push_input(1);
+        fork.push_input(10);
+        assert_eq!(pc.run_until_io().unwrap(), Status::Output(6));
+        assert_eq!(fork.run_until_io().unwrap(), Status::Output(15));
+        assert_eq!(pc.run_until_io().unwrap(), Status::NeedsInput);
+    }
+
+    #[test]
+    fn fork_keeps_memory_limit() {
+        let mut pc = IntcodePC::synchronous(counter());
+        pc.set_memory_limit(12);
+        let mut fork = pc.fork();
+        fork.push_input(5);
+        assert!(matches!(fork.run_until_io(), Err(IntcodeError::MemoryLimit { addr: 12, limit: 12, .. })));
+        assert!(matches!(IntcodePC::restore(pc.snapshot()).run_until_io(), Ok(Status::NeedsInput)));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut pc = IntcodePC::synchronous(counter());
+        pc.push_input(3);
+        pc.push_input(4);
+        assert_eq!(pc.run_until_io().unwrap(), Status::Output(3));
+        let snapshot = pc.snapshot();
+        assert_eq!(snapshot.inputs, [4]);
+
+        let mut file = vec![];
+        snapshot.save(&mut file).unwrap();
+        let loaded = Snapshot::load(file.as_slice()).unwrap();
+        assert_eq!(loaded, snapshot);
+
+        let mut restored = IntcodePC::restore(loaded);
+        assert_eq!(restored.pc(), pc.pc());
+        assert_eq!(restored.run_until_io().unwrap(), Status::Output(7));
+        assert!(Snapshot::load("{}".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use rayon::prelude::*;
+
+use crate::fast::FastPC;
+use crate::intcode_pc::Status;
+use crate::symbolic::SymbolicPC;
+
+/// What a successful run has to end with.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Goal {
+    /// `addr` holds `value` after the program halts.
+    Memory { addr: usize, value: i64 },
+    /// The last output before halting is `value`.
+    Output(i64),
+}
+
+impl Goal {
+    fn value(self) -> i64 {
+        match self {
+            Goal::Memory { value, .. } | Goal::Output(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SearchError {
+    /// No candidate reaches the goal.
+    NotFound,
+    /// The parameter space has more points than a `usize` can count.
+    TooLarge,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::NotFound => write!(f, "no candidate reaches the goal"),
+            SearchError::TooLarge => write!(f, "too many candidates to search"),
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+/// Searches for values of patched memory cells that make a program reach a
+/// `Goal`, e.g. day2's noun and verb. Candidates are tried in parallel on
+/// `FastPC`s and the search stops at the first hit.
+#[derive(Debug, Clone)]
+pub struct Search {
+    program: Vec<i64>,
+    params: Vec<(usize, RangeInclusive<i64>)>,
+    inputs: Vec<i64>,
+    max_steps: u64,
+    linear: bool,
+}
+
+impl Search {
+    pub fn new(program: &[i64]) -> Self {
+        Self {
+            program: program.to_vec(),
+            params: vec![],
+            inputs: vec![],
+            max_steps: 1_000_000,
+            linear: false,
+        }
+    }
+
+    /// Patches `addr` with every value of `range`. An empty range leaves
+    /// nothing to find.
+    pub fn param(mut self, addr: usize, range: RangeInclusive<i64>) -> Self {
+        self.params.push((addr, range));
+        self
+    }
+
+    pub fn input(mut self, value: i64) -> Self {
+        self.inputs.push(value);
+        self
+    }
+
+    /// Candidates running longer than `steps` instructions are rejected.
+    pub fn max_steps(mut self, steps: u64) -> Self {
+        self.max_steps = steps;
+        self
+    }
+
+    /// Assume the goal cell is a linear function of the parameters. The
+    /// coefficients come from running the program symbolically, or from
+    /// probing when that fails, and the last parameter is then solved for
+    /// instead of searched. Falls back to the plain search if the program
+    /// turns out not to be linear; answers are always confirmed by an actual
+    /// run.
+    pub fn linear(mut self, linear: bool) -> Self {
+        self.linear = linear;
+        self
+    }
+
+    /// Size of the parameter space, `None` if it doesn't fit in a `usize`.
+    pub fn candidates(&self) -> Option<usize> {
+        count(&self.params)
+    }
+
+    /// Runs the program with `values` patched in and returns what `goal`
+    /// looks at, `None` if it fails, runs too long or never gets there.
+    pub fn eval(&self, values: &[i64], goal: Goal) -> Option<i64> {
+        let mut program = self.program.clone();
+        for ((addr, _), &value) in self.params.iter().zip(values) {
+            if program.len() <= *addr {
+                program.resize(addr + 1, 0);
+            }
+            program[*addr] = value;
+        }
+        let mut pc = FastPC::new(program);
+        self.inputs.iter().for_each(|&v| pc.push_input(v));
+        let mut last = None;
+        for _ in 0..self.max_steps {
+            match pc.step().ok()? {
+                Status::Running => (),
+                Status::Output(v) => last = Some(v),
+                Status::Halted => return match goal {
+                    Goal::Memory { addr, .. } => Some(pc.peek(addr)),
+                    Goal::Output(_) => last,
+                },
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    fn hits(&self, values: &[i64], goal: Goal) -> bool {
+        self.eval(values, goal) == Some(goal.value())
+    }
+
+    /// Parameter values reaching `goal`, in the order the params were
+    /// added. Without `linear` the result is the first hit in row-major
+    /// order of the parameter space.
+    pub fn solve(&self, goal: Goal) -> Result<Vec<i64>, SearchError> {
+        let candidates = self.candidates().ok_or(SearchError::TooLarge)?;
+        if candidates == 0 {
+            return Err(SearchError::NotFound);
+        }
+        let model = if self.linear { self.fit(goal) } else { None };
+        model.and_then(|model| self.solve_linear(&model, goal)).or_else(|| {
+            (0..candidates).into_par_iter()
+                .map(|n| self.values(n, &self.params))
+                .find_first(|values| self.hits(values, goal))
+        }).ok_or(SearchError::NotFound)
+    }
+
+    /// Mixed radix decoding of the `n`th point of `params`.
+    fn values(&self, mut n: usize, params: &[(usize, RangeInclusive<i64>)]) -> Vec<i64> {
+        let mut values = vec![0; params.len()];
+        for (value, (_, range)) in values.iter_mut().zip(params).rev() {
+            let len = len(range).unwrap();
+            *value = range.start() + (n % len) as i64;
+            n /= len;
+        }
+        values
+    }
+
+    /// The goal cell as a linear function of the parameters, measured from
+    /// their lowest values.
+    fn fit(&self, goal: Goal) -> Option<Linear> {
+        self.fit_symbolic(goal).or_else(|| self.fit_probing(goal))
+    }
+
+    /// Exact model, as long as no branch depends on the parameters.
+    fn fit_symbolic(&self, goal: Goal) -> Option<Linear> {
+        let mut pc = SymbolicPC::new(&self.program);
+        let names: Vec<String> = self.params.iter().map(|(addr, _)| format!("[{}]", addr)).collect();
+        for ((addr, _), name) in self.params.iter().zip(&names) {
+            pc.set_symbol(*addr, name);
+        }
+        self.inputs.iter().for_each(|&v| pc.push_input(v));
+        pc.run(self.max_steps).ok()?;
+        if !pc.branches().is_empty() {
+            return None;
+        }
+        let result = match goal {
+            Goal::Memory { addr, .. } => pc.cell(addr),
+            Goal::Output(_) => pc.outputs().last()?.clone(),
+        };
+        let form = result.linear()?;
+        let low: Vec<i64> = self.params.iter().map(|(_, r)| *r.start()).collect();
+        let coefficients: Vec<i64> = names.iter().map(|name| form.coefficient(name)).collect();
+        let base = low.iter().zip(&coefficients)
+            .try_fold(form.constant, |acc, (v, c)| acc.checked_add(c.checked_mul(*v)?))?;
+        Some(Linear { low, base, coefficients }).filter(|model| model.coefficients.last().copied().unwrap_or(0) != 0)
+    }
+
+    /// Model measured from the lowest values and one step up each parameter,
+    /// checked on two more points.
+    fn fit_probing(&self, goal: Goal) -> Option<Linear> {
+        let low: Vec<i64> = self.params.iter().map(|(_, r)| *r.start()).collect();
+        let base = self.eval(&low, goal)?;
+        let mut coefficients = vec![0; low.len()];
+        for (i, (_, range)) in self.params.iter().enumerate() {
+            if len(range)? > 1 {
+                let mut probe = low.clone();
+                probe[i] += 1;
+                coefficients[i] = self.eval(&probe, goal)?.checked_sub(base)?;
+            }
+        }
+        let model = Linear { low, base, coefficients };
+        if model.coefficients.last().copied().unwrap_or(0) == 0 {
+            return None;
+        }
+        let high: Vec<i64> = self.params.iter().map(|(_, r)| *r.end()).collect();
+        let middle: Vec<i64> = self.params.iter().map(|(_, r)| r.start() + (r.end() - r.start()) / 2).collect();
+        for probe in [high, middle].iter() {
+            if self.eval(probe, goal) != model.at(probe) {
+                return None;
+            }
+        }
+        Some(model)
+    }
+
+    fn solve_linear(&self, model: &Linear, goal: Goal) -> Option<Vec<i64>> {
+        let (rest, last) = self.params.split_at(self.params.len() - 1);
+        let (last, c) = (&last[0].1, *model.coefficients.last().unwrap());
+        (0..count(rest)?).into_par_iter()
+            .filter_map(|n| {
+                let mut values = self.values(n, rest);
+                values.push(*last.start());
+                let missing = goal.value().checked_sub(model.at(&values)?)?;
+                if missing % c != 0 {
+                    return None;
+                }
+                let value = last.start().checked_add(missing / c)?;
+                *values.last_mut().unwrap() = value;
+                Some(values).filter(|_| last.contains(&value))
+            })
+            .find_first(|values| self.hits(values, goal))
+    }
+}
+
+struct Linear {
+    low: Vec<i64>,
+    base: i64,
+    coefficients: Vec<i64>,
+}
+
+impl Linear {
+    fn at(&self, values: &[i64]) -> Option<i64> {
+        values.iter().zip(&self.low).zip(&self.coefficients)
+            .try_fold(self.base, |acc, ((v, low), c)| acc.checked_add(c.checked_mul(v - low)?))
+    }
+}
+
+/// Number of values in `range`, `None` if that doesn't fit in a `usize`.
+fn len(range: &RangeInclusive<i64>) -> Option<usize> {
+    if range.is_empty() {
+        return Some(0);
+    }
+    let span = range.end().checked_sub(*range.start())?;
+    usize::try_from(span).ok()?.checked_add(1)
+}
+
+fn count(params: &[(usize, RangeInclusive<i64>)]) -> Option<usize> {
+    params.iter().try_fold(1usize, |acc, (_, r)| acc.checked_mul(len(r)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::intcode_pc::load_prog;
+
+    const TARGET: Goal = Goal::Memory { addr: 0, value: 19_690_720 };
+
+    fn day2() -> Search {
+        Search::new(&load_prog(include_str!("../../day2/input")))
+            .param(1, 0..=99)
+            .param(2, 0..=99)
+    }
+
+    #[test]
+    fn brute_force() {
+        let search = day2();
+        assert_eq!(search.candidates(), Some(10_000));
+        assert_eq!(search.eval(&[12, 2], Goal::Output(0)), None);
+        let found = search.solve(TARGET).unwrap();
+        assert_eq!(search.eval(&found, TARGET), Some(19_690_720));
+        assert_eq!(search.solve(Goal::Memory { addr: 0, value: -1 }), Err(SearchError::NotFound));
+    }
+
+    #[test]
+    fn linear_agrees() {
+        let search = day2().linear(true);
+        assert!(search.fit_symbolic(TARGET).is_some());
+        let (exact, probed) = (search.fit_symbolic(TARGET).unwrap(), search.fit_probing(TARGET).unwrap());
+        assert_eq!((exact.base, exact.coefficients), (probed.base, probed.coefficients));
+        assert_eq!(search.solve(TARGET), day2().solve(TARGET));
+    }
+
+    #[test]
+    fn nonlinear_falls_back() {
+        let prog = assemble("
+                    mul  [a], [b], [result]
+                    out  [result]
+                    hlt
+            a:      data 0
+            b:      data 0
+            result: data 0
+        ").unwrap();
+        let search = Search::new(&prog).param(7, 1..=20).param(8, 1..=20).linear(true);
+        assert!(search.fit(Goal::Output(91)).is_none());
+        assert_eq!(search.solve(Goal::Output(91)), Ok(vec![7, 13]));
+    }
+
+    #[test]
+    fn misleading_probes_fall_back() {
+        // Linear at every probed point, but only 7 reaches 100.
+        let prog = assemble("
+                    eq   [p], #7, [flag]
+                    jit  [flag], #seven
+                    out  [p]
+                    hlt
+            seven:  out  #100
+                    hlt
+            p:      data 0
+            flag:   data 0
+        ").unwrap();
+        let search = Search::new(&prog).param(13, 0..=10).linear(true);
+        assert!(search.fit(Goal::Output(100)).is_some());
+        assert_eq!(search.solve(Goal::Output(100)), Ok(vec![7]));
+    }
+
+    #[test]
+    fn oversized_spaces() {
+        let search = Search::new(&[99]).param(0, i64::MIN..=i64::MAX);
+        assert_eq!(search.candidates(), None);
+        assert_eq!(search.solve(Goal::Output(0)), Err(SearchError::TooLarge));
+        let search = Search::new(&[99]).param(0, 0..=i64::MAX).param(1, 0..=i64::MAX);
+        assert_eq!(search.solve(Goal::Output(0)), Err(SearchError::TooLarge));
+        let search = Search::new(&[99]).param(0, RangeInclusive::new(5, 4));
+        assert_eq!(search.candidates(), Some(0));
+        assert_eq!(search.solve(Goal::Output(0)), Err(SearchError::NotFound));
+    }
+
+    #[test]
+    fn endless_candidates_are_rejected() {
+        // Patching 1 into the jump condition loops forever.
+        let prog = assemble("
+            loop:   jit  [flag], #loop
+                    out  #5
+                    hlt
+            flag:   data 1
+        ").unwrap();
+        let search = Search::new(&prog).param(6, 0..=1).max_steps(100);
+        assert_eq!(search.eval(&[1], Goal::Output(5)), None);
+        assert_eq!(search.solve(Goal::Output(5)), Ok(vec![0]));
+    }
+}
@@ -0,0 +1,596 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::ops;
+
+use crate::error::IntcodeError;
+use crate::intcode_pc::{Mode, Order};
+
+/// Value computed from symbols. Built with `+`, `*`, `Expr::lt` and `Expr::eq`,
+/// keep anything linear in the canonical `3*a + b + 7` shape.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Expr {
+    Const(i64),
+    Sym(String),
+    /// Read from an address that depends on symbols.
+    Load(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Lt(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn sym(name: &str) -> Self {
+        Expr::Sym(name.to_owned())
+    }
+
+    pub fn lt(a: Expr, b: Expr) -> Self {
+        Expr::Lt(Box::new(a), Box::new(b)).simplify()
+    }
+
+    pub fn eq(a: Expr, b: Expr) -> Self {
+        Expr::Eq(Box::new(a), Box::new(b)).simplify()
+    }
+
+    fn simplify(self) -> Self {
+        match self.linear() {
+            Some(form) => form.into_expr(),
+            None => self,
+        }
+    }
+
+    pub fn as_const(&self) -> Option<i64> {
+        match *self {
+            Expr::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The expression as a sum of symbols times constants, `None` if it
+    /// multiplies symbols together, compares them or reads through them.
+    pub fn linear(&self) -> Option<LinearForm> {
+        match self {
+            Expr::Const(value) => Some(LinearForm::constant(*value)),
+            Expr::Sym(name) => {
+                let mut form = LinearForm::default();
+                form.terms.insert(name.clone(), 1);
+                Some(form)
+            },
+            Expr::Load(_) => None,
+            Expr::Add(a, b) => a.linear()?.add(&b.linear()?),
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                match (a.as_const(), b.as_const()) {
+                    (Some(by), _) => b.scale(by),
+                    (_, Some(by)) => a.scale(by),
+                    _ => None,
+                }
+            },
+            Expr::Lt(a, b) | Expr::Eq(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                let result = match (a.as_const(), b.as_const()) {
+                    (Some(a), Some(b)) if matches!(self, Expr::Lt(..)) => a < b,
+                    (Some(a), Some(b)) => a == b,
+                    _ if a == b => matches!(self, Expr::Eq(..)),
+                    _ => return None,
+                };
+                Some(LinearForm::constant(result as i64))
+            },
+        }
+    }
+
+    pub fn symbols(&self) -> BTreeSet<String> {
+        let mut symbols = BTreeSet::new();
+        self.collect_symbols(&mut symbols);
+        symbols
+    }
+
+    fn collect_symbols(&self, symbols: &mut BTreeSet<String>) {
+        match self {
+            Expr::Const(_) => (),
+            Expr::Sym(name) => {
+                symbols.insert(name.clone());
+            },
+            Expr::Load(addr) => addr.collect_symbols(symbols),
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::Lt(a, b) | Expr::Eq(a, b) => {
+                a.collect_symbols(symbols);
+                b.collect_symbols(symbols);
+            },
+        }
+    }
+
+    /// Value with the symbols set to `values`, `None` if one is missing, the
+    /// arithmetic overflows or the expression reads through a symbol.
+    pub fn eval(&self, values: &HashMap<String, i64>) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            Expr::Sym(name) => values.get(name).copied(),
+            Expr::Load(_) => None,
+            Expr::Add(a, b) => a.eval(values)?.checked_add(b.eval(values)?),
+            Expr::Mul(a, b) => a.eval(values)?.checked_mul(b.eval(values)?),
+            Expr::Lt(a, b) => Some((a.eval(values)? < b.eval(values)?) as i64),
+            Expr::Eq(a, b) => Some((a.eval(values)? == b.eval(values)?) as i64),
+        }
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(value: i64) -> Self {
+        Expr::Const(value)
+    }
+}
+
+impl ops::Add for Expr {
+    type Output = Expr;
+
+    fn add(self, other: Expr) -> Expr {
+        Expr::Add(Box::new(self), Box::new(other)).simplify()
+    }
+}
+
+impl ops::Mul for Expr {
+    type Output = Expr;
+
+    fn mul(self, other: Expr) -> Expr {
+        Expr::Mul(Box::new(self), Box::new(other)).simplify()
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let factor = |e: &Expr| match e {
+            Expr::Add(..) => format!("({})", e),
+            _ => e.to_string(),
+        };
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Sym(name) => write!(f, "{}", name),
+            Expr::Load(addr) => write!(f, "mem[{}]", addr),
+            Expr::Add(a, b) => write!(f, "{} + {}", a, b),
+            Expr::Mul(a, b) => write!(f, "{}*{}", factor(a), factor(b)),
+            Expr::Lt(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Eq(a, b) => write!(f, "({} == {})", a, b),
+        }
+    }
+}
+
+/// `constant + sum(coefficient * symbol)`, zero coefficients are dropped.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct LinearForm {
+    pub terms: BTreeMap<String, i64>,
+    pub constant: i64,
+}
+
+impl LinearForm {
+    fn constant(value: i64) -> Self {
+        Self { terms: BTreeMap::new(), constant: value }
+    }
+
+    fn as_const(&self) -> Option<i64> {
+        Some(self.constant).filter(|_| self.terms.is_empty())
+    }
+
+    fn add(mut self, other: &LinearForm) -> Option<Self> {
+        self.constant = self.constant.checked_add(other.constant)?;
+        for (name, c) in other.terms.iter() {
+            let sum = self.terms.get(name).unwrap_or(&0).checked_add(*c)?;
+            self.terms.insert(name.clone(), sum);
+        }
+        self.terms.retain(|_, c| *c != 0);
+        Some(self)
+    }
+
+    fn scale(mut self, by: i64) -> Option<Self> {
+        self.constant = self.constant.checked_mul(by)?;
+        for c in self.terms.values_mut() {
+            *c = c.checked_mul(by)?;
+        }
+        self.terms.retain(|_, c| *c != 0);
+        Some(self)
+    }
+
+    pub fn coefficient(&self, name: &str) -> i64 {
+        self.terms.get(name).copied().unwrap_or(0)
+    }
+
+    /// Value of `name` that makes the form equal `target`, with every other
+    /// symbol taken from `values`. `None` if there is no integer solution.
+    pub fn solve_for(&self, name: &str, target: i64, values: &HashMap<String, i64>) -> Option<i64> {
+        let c = self.coefficient(name);
+        let mut rest = target.checked_sub(self.constant)?;
+        for (other, k) in self.terms.iter().filter(|&(other, _)| other != name) {
+            rest = rest.checked_sub(k.checked_mul(*values.get(other)?)?)?;
+        }
+        match c {
+            0 => None,
+            _ if rest % c != 0 => None,
+            _ => Some(rest / c),
+        }
+    }
+
+    pub fn into_expr(self) -> Expr {
+        let constant = Some(Expr::Const(self.constant)).filter(|_| self.constant != 0);
+        let terms = self.terms.into_iter().map(|(name, c)| match c {
+            1 => Expr::Sym(name),
+            _ => Expr::Mul(Box::new(Expr::Const(c)), Box::new(Expr::Sym(name))),
+        });
+        terms.chain(constant)
+            .fold(None, |acc, term| match acc {
+                None => Some(term),
+                Some(acc) => Some(Expr::Add(Box::new(acc), Box::new(term))),
+            })
+            .unwrap_or(Expr::Const(0))
+    }
+}
+
+/// Jump that went one way or the other depending on symbols.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Branch {
+    pub pc: usize,
+    pub condition: Expr,
+    pub taken: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SymbolicError {
+    Machine(IntcodeError),
+    /// The opcode at `pc` depends on symbols.
+    SymbolicCode { pc: usize, opcode: Expr },
+    /// A write, jump or relative base change with an address depending on
+    /// symbols.
+    SymbolicAddress { pc: usize, addr: Expr },
+    /// A branch on symbols that weren't given a value with `assume`.
+    UnresolvedBranch { pc: usize, condition: Expr },
+    StepLimit,
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolicError::Machine(e) => write!(f, "{}", e),
+            SymbolicError::SymbolicCode { pc, opcode } =>
+                write!(f, "opcode {} at pc {} depends on symbols", opcode, pc),
+            SymbolicError::SymbolicAddress { pc, addr } =>
+                write!(f, "address {} used at pc {} depends on symbols", addr, pc),
+            SymbolicError::UnresolvedBranch { pc, condition } =>
+                write!(f, "branch on {} at pc {} has no assumed outcome", condition, pc),
+            SymbolicError::StepLimit => write!(f, "step limit reached"),
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+impl From<IntcodeError> for SymbolicError {
+    fn from(e: IntcodeError) -> Self {
+        SymbolicError::Machine(e)
+    }
+}
+
+/// Interpreter running on `Expr`s. Code and addresses have to stay
+/// concrete, data may depend on symbols given to memory cells or inputs.
+/// Branches on symbols follow the values passed to `assume` and are
+/// recorded, results only hold for the path taken when there are any.
+pub struct SymbolicPC {
+    program: Vec<Expr>,
+    written: BTreeMap<usize, Expr>,
+    pc: usize,
+    rel_base: i64,
+    inputs: VecDeque<Expr>,
+    assumptions: HashMap<String, i64>,
+    branches: Vec<Branch>,
+    outputs: Vec<Expr>,
+    limit: usize,
+}
+
+impl SymbolicPC {
+    pub fn new(program: &[i64]) -> Self {
+        Self {
+            program: program.iter().map(|&v| Expr::Const(v)).collect(),
+            written: BTreeMap::new(),
+            pc: 0,
+            rel_base: 0,
+            inputs: VecDeque::new(),
+            assumptions: HashMap::new(),
+            branches: vec![],
+            outputs: vec![],
+            limit: usize::MAX,
+        }
+    }
+
+    /// Replaces the value at `addr` with the symbol `name`.
+    pub fn set_symbol(&mut self, addr: usize, name: &str) {
+        self.store(addr, Expr::sym(name));
+    }
+
+    pub fn push_input(&mut self, value: impl Into<Expr>) {
+        self.inputs.push_back(value.into());
+    }
+
+    /// Value used to decide branches on `name`.
+    pub fn assume(&mut self, name: &str, value: i64) {
+        self.assumptions.insert(name.to_owned(), value);
+    }
+
+    /// Writes at or past `cells` fail with `IntcodeError::MemoryLimit`.
+    pub fn set_memory_limit(&mut self, cells: usize) {
+        self.limit = cells;
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn cell(&self, addr: usize) -> Expr {
+        self.program.get(addr).or_else(|| self.written.get(&addr)).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    /// The program cells followed by the ones written past it, by address.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, &Expr)> {
+        self.program.iter().enumerate().chain(self.written.iter().map(|(&addr, e)| (addr, e)))
+    }
+
+    pub fn outputs(&self) -> &[Expr] {
+        &self.outputs
+    }
+
+    pub fn branches(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    fn store(&mut self, addr: usize, value: Expr) {
+        match self.program.get_mut(addr) {
+            Some(cell) => *cell = value,
+            None => {
+                self.written.insert(addr, value);
+            },
+        }
+    }
+
+    fn write(&mut self, addr: usize, value: Expr, raw: i64) -> Result<(), SymbolicError> {
+        if addr >= self.limit {
+            return Err(IntcodeError::MemoryLimit { pc: self.pc, raw, addr, limit: self.limit }.into());
+        }
+        self.store(addr, value);
+        Ok(())
+    }
+
+    /// Concrete address out of `addr`, `None` if it depends on symbols.
+    fn address(&self, addr: &Expr, raw: i64) -> Result<Option<usize>, SymbolicError> {
+        match addr.as_const() {
+            Some(a) if a < 0 => Err(IntcodeError::NegativeAddress { pc: self.pc, raw, addr: a }.into()),
+            Some(a) => Ok(Some(a as usize)),
+            None => Ok(None),
+        }
+    }
+
+    fn concrete(&self, addr: Expr, raw: i64) -> Result<usize, SymbolicError> {
+        self.address(&addr, raw)?.ok_or(SymbolicError::SymbolicAddress { pc: self.pc, addr })
+    }
+
+    fn args(&self, order: &Order, raw: i64) -> Result<Vec<Expr>, SymbolicError> {
+        let relative = |arg: Expr| arg + Expr::Const(self.rel_base);
+        order.modes().into_iter()
+            .enumerate()
+            .map(|(n, mode)| {
+                let arg = self.cell(self.pc + n + 1);
+                let read = |addr: Expr| Ok(match self.address(&addr, raw)? {
+                    Some(addr) => self.cell(addr),
+                    None => Expr::Load(Box::new(addr)),
+                });
+                match mode {
+                    Mode::Position           => read(arg),
+                    Mode::Relative           => read(relative(arg)),
+                    Mode::Immediate          => Ok(arg),
+                    Mode::ReturnAddr         => Ok(arg),
+                    Mode::ReturnAddrRelative => Ok(relative(arg)),
+                }
+            })
+            .collect()
+    }
+
+    fn branch(&mut self, test: Expr, if_zero: bool) -> Result<bool, SymbolicError> {
+        let nonzero = match test.as_const() {
+            Some(value) => value != 0,
+            None => {
+                let nonzero = test.eval(&self.assumptions)
+                    .ok_or_else(|| SymbolicError::UnresolvedBranch { pc: self.pc, condition: test.clone() })?
+                    != 0;
+                self.branches.push(Branch { pc: self.pc, condition: test, taken: nonzero != if_zero });
+                nonzero
+            },
+        };
+        Ok(nonzero != if_zero)
+    }
+
+    /// Executes one instruction, `Ok(false)` once the program halted.
+    pub fn step(&mut self) -> Result<bool, SymbolicError> {
+        use Order::*;
+        let opcode = self.cell(self.pc);
+        let raw = opcode.as_const().ok_or(SymbolicError::SymbolicCode { pc: self.pc, opcode })?;
+        if raw == 99 {
+            return Ok(false);
+        }
+        let order = Order::new(raw, self.pc)?;
+        let mut args = self.args(&order, raw)?;
+        let overflow = IntcodeError::Overflow { pc: self.pc, raw };
+        match order {
+            Add {..} | Mul {..} | Lt {..} | Eq {..} => {
+                let addr = self.concrete(args.pop().unwrap(), raw)?;
+                let (a, b) = (args[0].clone(), args[1].clone());
+                let value = match (order, a.as_const(), b.as_const()) {
+                    (Add {..}, Some(x), Some(y)) => Expr::Const(x.checked_add(y).ok_or(overflow)?),
+                    (Mul {..}, Some(x), Some(y)) => Expr::Const(x.checked_mul(y).ok_or(overflow)?),
+                    (Add {..}, ..) => a + b,
+                    (Mul {..}, ..) => a * b,
+                    (Lt {..}, ..) => Expr::lt(a, b),
+                    _ => Expr::eq(a, b),
+                };
+                self.write(addr, value, raw)?;
+                self.pc += 4;
+            },
+            In {..} => {
+                if self.inputs.is_empty() {
+                    return Err(IntcodeError::InputClosed { pc: self.pc, raw }.into());
+                }
+                let addr = self.concrete(args.pop().unwrap(), raw)?;
+                let value = self.inputs.pop_front().unwrap();
+                self.write(addr, value, raw)?;
+                self.pc += 2;
+            },
+            Out {..} => {
+                self.outputs.push(args.pop().unwrap());
+                self.pc += 2;
+            },
+            Jit {..} | Jif {..} => {
+                let addr = args.pop().unwrap();
+                let if_zero = matches!(order, Jif {..});
+                if self.branch(args.pop().unwrap(), if_zero)? {
+                    self.pc = self.concrete(addr, raw)?;
+                } else {
+                    self.pc += 3;
+                }
+            },
+            Rbo {..} => {
+                let offset = args.pop().unwrap();
+                let offset = offset.as_const().ok_or(SymbolicError::SymbolicAddress { pc: self.pc, addr: offset })?;
+                self.rel_base = self.rel_base.checked_add(offset).ok_or(overflow)?;
+                self.pc += 2;
+            },
+        }
+        Ok(true)
+    }
+
+    /// Runs until the program halts. Running out of input is an error, the
+    /// inputs have to be queued upfront.
+    pub fn run(&mut self, max_steps: u64) -> Result<(), SymbolicError> {
+        for _ in 0..max_steps {
+            if !self.step()? {
+                return Ok(());
+            }
+        }
+        Err(SymbolicError::StepLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::intcode_pc::{load_prog, IntcodePC};
+
+    fn values(pairs: &[(&str, i64)]) -> HashMap<String, i64> {
+        pairs.iter().map(|&(name, value)| (name.to_owned(), value)).collect()
+    }
+
+    #[test]
+    fn simplification() {
+        let (x, y) = (Expr::sym("x"), Expr::sym("y"));
+        let e = Expr::Const(3) * (x.clone() + Expr::Const(2)) + y.clone();
+        assert_eq!(e.to_string(), "3*x + y + 6");
+        assert_eq!((e.clone() + Expr::Const(-3) * x.clone()).to_string(), "y + 6");
+        assert_eq!(Expr::eq(e.clone(), e.clone()), Expr::Const(1));
+        assert_eq!((x.clone() * y.clone()).to_string(), "x*y");
+        assert!((x.clone() * y).linear().is_none());
+        assert_eq!(Expr::lt(x, Expr::Const(4)).eval(&values(&[("x", 3)])), Some(1));
+        assert_eq!(e.symbols().into_iter().collect::<Vec<_>>(), ["x", "y"]);
+    }
+
+    #[test]
+    fn day2_is_linear() {
+        let program = load_prog(include_str!("../../day2/input"));
+        let mut pc = SymbolicPC::new(&program);
+        pc.set_symbol(1, "noun");
+        pc.set_symbol(2, "verb");
+        pc.run(10_000).unwrap();
+        assert!(pc.branches().is_empty());
+        let form = pc.cell(0).linear().unwrap();
+        assert_eq!(form.coefficient("verb"), 1);
+
+        let mut patched = program.clone();
+        patched[1] = 12;
+        patched[2] = 2;
+        let expected = IntcodePC::new(patched.clone()).0.run().unwrap().0[0];
+        assert_eq!(pc.cell(0).eval(&values(&[("noun", 12), ("verb", 2)])), Some(expected));
+
+        let (noun, verb) = (0..=99)
+            .find_map(|noun| {
+                let verb = form.solve_for("verb", 19_690_720, &values(&[("noun", noun)]))?;
+                Some((noun, verb)).filter(|_| (0..=99).contains(&verb))
+            })
+            .unwrap();
+        patched[1] = noun;
+        patched[2] = verb;
+        assert_eq!(IntcodePC::new(patched).0.run().unwrap().0[0], 19_690_720);
+    }
+
+    #[test]
+    fn symbolic_inputs() {
+        let prog = assemble("
+                    in   [x]
+                    mul  [x], #3, [x]
+                    add  [x], #1, [x]
+                    out  [x]
+                    hlt
+            x:      data 0
+        ").unwrap();
+        let mut pc = SymbolicPC::new(&prog);
+        pc.push_input(Expr::sym("x"));
+        pc.run(100).unwrap();
+        assert_eq!(pc.outputs()[0].to_string(), "3*x + 1");
+        assert_eq!(pc.outputs()[0].linear().unwrap().solve_for("x", 10, &HashMap::new()), Some(3));
+        assert_eq!(pc.outputs()[0].linear().unwrap().solve_for("x", 11, &HashMap::new()), None);
+    }
+
+    #[test]
+    fn branches() {
+        let prog = assemble("
+                    in   [x]
+                    eq   [x], #5, [tmp]
+                    jit  [tmp], #five
+                    out  #0
+                    hlt
+            five:   out  [x]
+                    hlt
+            x:      data 0
+            tmp:    data 0
+        ").unwrap();
+        let mut pc = SymbolicPC::new(&prog);
+        pc.push_input(Expr::sym("x"));
+        assert_eq!(pc.run(100), Err(SymbolicError::UnresolvedBranch {
+            pc: 6,
+            condition: Expr::eq(Expr::sym("x"), Expr::Const(5)),
+        }));
+
+        let mut pc = SymbolicPC::new(&prog);
+        pc.push_input(Expr::sym("x"));
+        pc.assume("x", 5);
+        pc.run(100).unwrap();
+        assert_eq!(pc.outputs(), [Expr::sym("x")]);
+        assert_eq!(pc.branches().len(), 1);
+        assert!(pc.branches()[0].taken);
+        assert_eq!(pc.branches()[0].condition.to_string(), "(x == 5)");
+    }
+
+    #[test]
+    fn symbolic_addresses() {
+        let mut pc = SymbolicPC::new(&[1, 0, 0, 7, 99]);
+        pc.set_symbol(1, "a");
+        pc.step().unwrap();
+        assert_eq!(pc.cell(7).to_string(), "mem[a] + 1");
+        assert!(pc.cell(7).linear().is_none());
+
+        let mut pc = SymbolicPC::new(&[1101, 1, 1, 0, 99]);
+        pc.set_symbol(3, "a");
+        assert_eq!(pc.step(), Err(SymbolicError::SymbolicAddress { pc: 0, addr: Expr::sym("a") }));
+    }
+
+    #[test]
+    fn far_writes() {
+        let mut pc = SymbolicPC::new(&[1101, 2, 3, 1_000_000_000_000, 99]);
+        pc.run(10).unwrap();
+        assert_eq!(pc.cell(1_000_000_000_000), Expr::Const(5));
+        assert_eq!(pc.cells().count(), 6);
+    }
+}
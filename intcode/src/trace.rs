@@ -0,0 +1,245 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::IntcodeError;
+use crate::intcode_pc::{IntcodePC, Status, Tracer};
+
+/// A single executed instruction with its arguments already resolved
+/// through their parameter modes.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct TraceEvent {
+    pub pc: usize,
+    pub raw: i64,
+    pub op: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write: Option<(usize, i64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<i64>,
+}
+
+/// Where a `json_lines` tracer leaves the error that made it stop.
+#[derive(Debug, Clone, Default)]
+pub struct TraceError(Arc<Mutex<Option<io::Error>>>);
+
+impl TraceError {
+    pub fn take(&self) -> Option<io::Error> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Tracer writing one JSON object per executed instruction, flushing the
+/// sink on halt. The first failed write stops tracing, the machine keeps
+/// running and the error waits in the returned `TraceError`.
+pub fn json_lines(mut sink: impl Write + Send + 'static) -> (Tracer, TraceError) {
+    let error = TraceError::default();
+    let slot = error.clone();
+    let mut failed = false;
+    let tracer = Box::new(move |event: &TraceEvent| {
+        if failed {
+            return;
+        }
+        let written = serde_json::to_writer(&mut sink, event).map_err(io::Error::from)
+            .and_then(|_| writeln!(sink))
+            .and_then(|_| if event.op == "hlt" { sink.flush() } else { Ok(()) });
+        if let Err(e) = written {
+            failed = true;
+            *slot.0.lock().unwrap() = Some(e);
+        }
+    });
+    (tracer, error)
+}
+
+pub fn read_trace(trace: impl BufRead) -> Result<Vec<TraceEvent>, ReplayError> {
+    trace.lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|(n, line)| {
+            let line = line.map_err(ReplayError::Io)?;
+            serde_json::from_str(&line)
+                .map_err(|e| ReplayError::Parse { line: n + 1, message: e.to_string() })
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    Machine(IntcodeError),
+    Diverged { step: usize, expected: Box<Option<TraceEvent>>, actual: Box<Option<TraceEvent>> },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "couldn't read the trace: {}", e),
+            ReplayError::Parse { line, message } =>
+                write!(f, "invalid trace event on line {}: {}", line, message),
+            ReplayError::Machine(e) => write!(f, "{}", e),
+            ReplayError::Diverged { step, expected, actual } =>
+                write!(f, "diverged at step {}: expected {:?}, got {:?}", step, expected, actual),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+/// Re-runs `program` feeding it the inputs recorded in `events` and checks
+/// every executed instruction against the recording. Returns the outputs.
+pub fn replay(program: Vec<i64>, events: &[TraceEvent]) -> Result<Vec<i64>, ReplayError> {
+    let mut pc = IntcodePC::synchronous(program);
+    events.iter().filter_map(|e| e.input).for_each(|v| pc.push_input(v));
+    let mut outputs = vec![];
+    for (step, expected) in events.iter().enumerate() {
+        let (status, actual) = pc.step_traced().map_err(ReplayError::Machine)?;
+        if actual.as_ref() != Some(expected) {
+            return Err(ReplayError::Diverged {
+                step,
+                expected: Box::new(Some(expected.clone())),
+                actual: Box::new(actual),
+            });
+        }
+        if let Status::Output(v) = status {
+            outputs.push(v);
+        }
+    }
+    if let Some(last) = events.last() {
+        if last.op != "hlt" {
+            let (_, actual) = pc.step_traced().map_err(ReplayError::Machine)?;
+            return Err(ReplayError::Diverged {
+                step: events.len(),
+                expected: Box::new(None),
+                actual: Box::new(actual),
+            });
+        }
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn double_prog() -> Vec<i64> {
+        assemble("
+            loop:   in   [value]
+                    jif  [value], #end
+                    mul  [value], #2, [value]
+                    out  [value]
+                    jit  #1, #loop
+            end:    hlt
+            value:  data 0
+        ").unwrap()
+    }
+
+    fn record(prog: Vec<i64>, inputs: &[i64]) -> (Vec<i64>, String) {
+        let buffer = SharedBuffer::default();
+        let mut pc = IntcodePC::synchronous(prog);
+        let (tracer, error) = json_lines(buffer.clone());
+        pc.set_tracer(tracer);
+        inputs.iter().for_each(|&v| pc.push_input(v));
+        let outputs = pc.run().unwrap().1;
+        assert!(error.take().is_none());
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        (outputs, trace)
+    }
+
+    #[test]
+    fn records_instructions() {
+        let (outputs, trace) = record(double_prog(), &[3, 0]);
+        assert_eq!(outputs, [6]);
+        let events = read_trace(trace.as_bytes()).unwrap();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[0], TraceEvent {
+            pc: 0, raw: 3, op: "in".to_owned(), args: vec![15],
+            write: Some((15, 3)), input: Some(3), output: None,
+        });
+        assert_eq!(events[2].write, Some((15, 6)));
+        assert_eq!(events[3].output, Some(6));
+        assert_eq!(trace.lines().last().unwrap(), r#"{"pc":14,"raw":99,"op":"hlt"}"#);
+    }
+
+    /// Takes `room` bytes, then reports a full disk.
+    struct Full(usize);
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 < buf.len() {
+                return Err(io::Error::other("disk full"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_errors_stop_tracing() {
+        let mut pc = IntcodePC::synchronous(double_prog());
+        let (tracer, error) = json_lines(Full(100));
+        pc.set_tracer(tracer);
+        [3, 5, 0].iter().for_each(|&v| pc.push_input(v));
+        assert_eq!(pc.run().unwrap().1, [6, 10]);
+        assert_eq!(error.take().unwrap().to_string(), "disk full");
+        assert!(error.take().is_none());
+    }
+
+    #[test]
+    fn replays_recording() {
+        let (outputs, trace) = record(double_prog(), &[3, 5, 7, 0]);
+        let events = read_trace(trace.as_bytes()).unwrap();
+        assert_eq!(replay(double_prog(), &events).unwrap(), outputs);
+    }
+
+    #[test]
+    fn reports_divergence() {
+        let (_, trace) = record(double_prog(), &[3, 0]);
+        let events = read_trace(trace.as_bytes()).unwrap();
+        let mut tripled = double_prog();
+        tripled[7] = 3;
+        match replay(tripled, &events) {
+            Err(ReplayError::Diverged { step: 2, expected, actual }) => {
+                assert_eq!(expected.unwrap().write, Some((15, 6)));
+                assert_eq!(actual.unwrap().write, Some((15, 9)));
+            },
+            other => panic!("Unexpected replay result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_trace() {
+        let (_, trace) = record(double_prog(), &[3, 0]);
+        let events = read_trace(trace.as_bytes()).unwrap();
+        match replay(double_prog(), &events[..4]) {
+            Err(ReplayError::Diverged { step: 4, expected, actual }) =>
+                assert!(expected.is_none() && actual.is_some()),
+            other => panic!("Unexpected replay result {:?}", other),
+        }
+        assert!(matches!(read_trace("{]".as_bytes()), Err(ReplayError::Parse { line: 1, .. })));
+    }
+}